@@ -0,0 +1,160 @@
+use crate::{download::Downloadable, generations::link_or_copy};
+use anyhow::{ensure, Result};
+use libium::{iter_ext::IterExt as _, HOME};
+use sha1::{Digest as _, Sha1};
+use std::{
+    fs::{create_dir_all, read_dir, remove_dir, remove_file, File},
+    io::copy,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+    time::{Duration, SystemTime},
+};
+
+/// The directory downloaded files are cached in, shared by all profiles and modpacks
+pub static CACHE_DIR: LazyLock<PathBuf> = LazyLock::new(|| {
+    HOME.join(".config")
+        .join("ferium")
+        .join(".cache")
+        .join("files")
+});
+
+/// The number of days a cached file has to be unused for before `ferium cache prune` deletes it
+pub const DEFAULT_MAX_AGE_DAYS: u64 = 30;
+
+/// Get the duration of `days` days
+pub fn days(days: u64) -> Duration {
+    Duration::from_secs(days.saturating_mul(24 * 60 * 60))
+}
+
+/// Get the path of the cached file with the SHA-1 hash `sha1`
+///
+/// Files are grouped into folders by the first two characters of their hash.
+pub fn path(sha1: &str) -> PathBuf {
+    let sha1 = sha1.to_ascii_lowercase();
+    CACHE_DIR.join(sha1.get(..2).unwrap_or("00")).join(sha1)
+}
+
+/// Mark the cached file at `path` as used now, so that it isn't pruned
+fn touch(path: &Path) -> Result<()> {
+    File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(SystemTime::now())?;
+    Ok(())
+}
+
+/// Hard link or copy the cached file with the SHA-1 hash `sha1` to `destination`
+///
+/// The cached file's hash is verified first, and it is deleted if it doesn't match.
+/// Returns whether the file was in the cache.
+pub fn install(sha1: &str, destination: &Path) -> Result<bool> {
+    let cached = path(sha1);
+    if !cached.is_file() {
+        return Ok(false);
+    }
+    let mut hasher = Sha1::new();
+    copy(&mut File::open(&cached)?, &mut hasher)?;
+    if !format!("{:x}", hasher.finalize()).eq_ignore_ascii_case(sha1) {
+        remove_file(cached)?;
+        return Ok(false);
+    }
+
+    if let Some(parent) = destination.parent() {
+        create_dir_all(parent)?;
+    }
+    if destination.exists() {
+        remove_file(destination)?;
+    }
+    link_or_copy(&cached, destination)?;
+    touch(&cached)?;
+    Ok(true)
+}
+
+/// Add the downloaded file at `source`, whose hash has been verified to be `sha1`, to the cache
+pub fn insert(sha1: &str, source: &Path) -> Result<()> {
+    let cached = path(sha1);
+    if cached.exists() {
+        return touch(&cached);
+    }
+    if let Some(parent) = cached.parent() {
+        create_dir_all(parent)?;
+    }
+    link_or_copy(source, &cached)
+}
+
+/// Check that every file in `to_download` is either already in `output_dir` or in the cache,
+/// so that they can be installed without accessing the internet
+///
+/// Files without a known hash, i.e. those from GitHub Releases, are never cached.
+pub fn ensure_available(output_dir: &Path, to_download: &[Downloadable]) -> Result<()> {
+    let missing = to_download
+        .iter()
+        .filter(|downloadable| {
+            !output_dir.join(&downloadable.output).is_file()
+                && !downloadable
+                    .sha1
+                    .as_ref()
+                    .is_some_and(|sha1| path(sha1).is_file())
+        })
+        .map(Downloadable::filename)
+        .collect_vec();
+    ensure!(
+        missing.is_empty(),
+        "\nThe following files have never been downloaded, upgrade while online to cache them\n  {}",
+        missing.iter().display("\n  ")
+    );
+    Ok(())
+}
+
+/// A file in the cache
+pub struct CachedFile {
+    pub path: PathBuf,
+    pub size: u64,
+    /// How long ago the file was last downloaded or installed
+    pub unused_for: Duration,
+}
+
+/// Get all the files in the cache
+pub fn list() -> Result<Vec<CachedFile>> {
+    let mut files = Vec::new();
+    if !CACHE_DIR.exists() {
+        return Ok(files);
+    }
+    for group in read_dir(&*CACHE_DIR)? {
+        let group = group?;
+        if !group.file_type()?.is_dir() {
+            continue;
+        }
+        for file in read_dir(group.path())? {
+            let file = file?;
+            let metadata = file.metadata()?;
+            if metadata.is_file() {
+                files.push(CachedFile {
+                    path: file.path(),
+                    size: metadata.len(),
+                    unused_for: metadata.modified()?.elapsed().unwrap_or(Duration::ZERO),
+                });
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Delete the cached files that have not been used for `max_age`
+///
+/// Files installed from the cache are hard links or copies, so this doesn't affect output directories.
+/// Returns the files that were deleted.
+pub fn prune(max_age: Duration) -> Result<Vec<CachedFile>> {
+    let mut pruned = Vec::new();
+    for file in list()? {
+        if file.unused_for >= max_age {
+            remove_file(&file.path)?;
+            if let Some(group) = file.path.parent() {
+                // Only succeeds if the group is empty
+                let _ = remove_dir(group);
+            }
+            pruned.push(file);
+        }
+    }
+    Ok(pruned)
+}
@@ -0,0 +1,334 @@
+use crate::lockfile::LockedFile;
+use anyhow::{anyhow, Result};
+use libium::{
+    config::structs::{ModIdentifier, ModLoader},
+    iter_ext::IterExt as _,
+    CURSEFORGE_API, GITHUB_API, MODRINTH_API,
+};
+use sha1::{Digest as _, Sha1};
+use std::{
+    collections::HashSet,
+    env::var,
+    fmt::Write as _,
+    fs::{read, read_dir, write},
+    io::{IsTerminal as _, Write as _},
+    path::Path,
+    process::{Command, Stdio},
+};
+use tokio::task::JoinSet;
+
+/// A mod whose installed file differs from the resolved one
+pub struct Change {
+    /// The installed file, if it is known from the previous lockfile
+    pub old: Option<LockedFile>,
+    pub new: LockedFile,
+}
+
+impl Change {
+    fn old_file_id(&self) -> &str {
+        self.old.as_ref().map_or("", |old| &old.file_id)
+    }
+}
+
+/// A version of a mod, with the changelog it was released with
+struct Version {
+    id: String,
+    title: String,
+    /// The UNIX timestamp the version was published at
+    published: i64,
+    changelog: Option<String>,
+}
+
+/// Get the mods whose file in `resolved` differs from the one in `previous` that is installed in `directory`
+///
+/// Mods that are not in `previous` (e.g. when upgrading without a lockfile for the first time)
+/// are compared with the installed files by filename and SHA-1 hash instead,
+/// and are considered changed with an unknown old version if neither matches.
+pub fn changes(
+    previous: &[LockedFile],
+    resolved: &[LockedFile],
+    directory: &Path,
+) -> Result<Vec<Change>> {
+    let mut installed_hashes = None;
+    let mut changes = Vec::new();
+    for new in resolved {
+        if let Some(old) = previous.iter().find(|old| old.project == new.project) {
+            if old.file_id != new.file_id && directory.join(&old.filename).is_file() {
+                changes.push(Change {
+                    old: Some(old.clone()),
+                    new: new.clone(),
+                });
+            }
+            continue;
+        }
+        if directory.join(&new.filename).is_file() {
+            continue;
+        }
+        if installed_hashes.is_none() {
+            installed_hashes = Some(hash_jars(directory)?);
+        }
+        if new.sha1.as_ref().map_or(true, |sha1| {
+            !installed_hashes
+                .as_ref()
+                .is_some_and(|hashes| hashes.contains(sha1))
+        }) {
+            changes.push(Change {
+                old: None,
+                new: new.clone(),
+            });
+        }
+    }
+    Ok(changes)
+}
+
+/// Get the SHA-1 hashes of the JAR files in `directory`
+fn hash_jars(directory: &Path) -> Result<HashSet<String>> {
+    let mut hashes = HashSet::new();
+    if !directory.is_dir() {
+        return Ok(hashes);
+    }
+    for entry in read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "jar") {
+            hashes.insert(format!("{:x}", Sha1::digest(read(&path)?)));
+        }
+    }
+    Ok(hashes)
+}
+
+/// Get the versions published after `old_id` up to and including `new_id`, newest first
+///
+/// If the old version cannot be found, only the new version is returned.
+fn between(mut versions: Vec<Version>, old_id: &str, new_id: &str) -> Vec<Version> {
+    let Some(new) = versions
+        .iter()
+        .find(|v| v.id == new_id)
+        .map(|v| v.published)
+    else {
+        return vec![];
+    };
+    let old = versions
+        .iter()
+        .find(|v| v.id == old_id)
+        .map_or(new - 1, |v| v.published);
+    versions.retain(|v| old < v.published && v.published <= new);
+    versions.sort_unstable_by_key(|v| std::cmp::Reverse(v.published));
+    versions
+}
+
+/// Convert a CurseForge changelog from HTML to plain text
+fn strip_html(html: &str) -> String {
+    let mut text = String::new();
+    let mut tag = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        if in_tag {
+            if c == '>' {
+                in_tag = false;
+                if ["br", "br/", "br /", "/p", "/li", "/div"].contains(&tag.to_lowercase().as_str())
+                {
+                    text.push('\n');
+                }
+            } else {
+                tag.push(c);
+            }
+        } else if c == '<' {
+            in_tag = true;
+            tag.clear();
+        } else {
+            text.push(c);
+        }
+    }
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+/// Get the versions of the mod in `change` between the installed and the resolved file, with their changelogs
+///
+/// Only versions for `loader` (in lowercase) are considered if it is provided.
+async fn fetch_versions(change: &Change, loader: Option<&str>) -> Result<Vec<Version>> {
+    Ok(match &change.new.project {
+        ModIdentifier::ModrinthProject(id) | ModIdentifier::PinnedModrinthProject(id, _) => {
+            let versions = MODRINTH_API
+                .list_versions(id)
+                .await?
+                .into_iter()
+                .filter(|version| {
+                    loader.map_or(true, |loader| {
+                        version
+                            .loaders
+                            .iter()
+                            .any(|version_loader| version_loader == loader)
+                    })
+                })
+                .map(|version| Version {
+                    id: version.id,
+                    title: version.name,
+                    published: version.date_published.timestamp(),
+                    changelog: version.changelog,
+                })
+                .collect_vec();
+            between(versions, change.old_file_id(), &change.new.file_id)
+        }
+        ModIdentifier::CurseForgeProject(id) => {
+            let files = CURSEFORGE_API
+                .get_mod_files(*id)
+                .await?
+                .into_iter()
+                .filter(|file| {
+                    loader.map_or(true, |loader| {
+                        file.game_versions
+                            .iter()
+                            .any(|version| version.eq_ignore_ascii_case(loader))
+                    })
+                })
+                .map(|file| Version {
+                    id: file.id.to_string(),
+                    title: file.display_name,
+                    published: file.file_date.timestamp(),
+                    changelog: None,
+                })
+                .collect_vec();
+            let mut tasks = JoinSet::new();
+            for (index, version) in between(files, change.old_file_id(), &change.new.file_id)
+                .into_iter()
+                .enumerate()
+            {
+                let id = *id;
+                tasks.spawn(async move {
+                    let mut version = version;
+                    if let Ok(file_id) = version.id.parse() {
+                        version.changelog = Some(strip_html(
+                            &CURSEFORGE_API.get_mod_file_changelog(id, file_id).await?,
+                        ));
+                    }
+                    Ok::<_, anyhow::Error>((index, version))
+                });
+            }
+            // Restore the order of the versions, newest first
+            let mut versions = tasks
+                .join_all()
+                .await
+                .into_iter()
+                .collect::<Result<Vec<_>>>()?;
+            versions.sort_unstable_by_key(|(index, _)| *index);
+            versions
+                .into_iter()
+                .map(|(_, version)| version)
+                .collect_vec()
+        }
+        ModIdentifier::GitHubRepository(owner, repo) => {
+            let releases = GITHUB_API
+                .repos(owner, repo)
+                .releases()
+                .list()
+                .per_page(100)
+                .send()
+                .await?
+                .items
+                .into_iter()
+                .filter_map(|release| {
+                    Some(Version {
+                        title: release.name.unwrap_or_else(|| release.tag_name.clone()),
+                        id: release.tag_name,
+                        published: release.published_at?.timestamp(),
+                        changelog: release.body,
+                    })
+                })
+                .collect_vec();
+            between(releases, change.old_file_id(), &change.new.file_id)
+        }
+        _ => vec![],
+    })
+}
+
+/// Fetch the changelogs of every version between the installed and resolved files of the `changes`,
+/// and format them as Markdown
+///
+/// Only versions for `mod_loader` are considered if it is provided.
+pub async fn fetch(changes: Vec<Change>, mod_loader: Option<&ModLoader>) -> Result<String> {
+    let loader = mod_loader.map(|loader| loader.to_string().to_lowercase());
+    let mut tasks = JoinSet::new();
+    for change in changes {
+        let loader = loader.clone();
+        tasks.spawn(async move {
+            let versions = fetch_versions(&change, loader.as_deref()).await;
+            (change, versions)
+        });
+    }
+
+    let mut sections = Vec::new();
+    for (change, versions) in tasks.join_all().await {
+        let mut section = format!(
+            "# {}\n\n{} → {}\n",
+            change.new.name,
+            change
+                .old
+                .as_ref()
+                .map_or("Unknown installed version", |old| &old.filename),
+            change.new.filename
+        );
+        match versions {
+            Ok(versions) => {
+                for version in versions {
+                    writeln!(
+                        section,
+                        "\n## {}\n\n{}",
+                        version.title,
+                        version
+                            .changelog
+                            .as_deref()
+                            .map(str::trim)
+                            .filter(|changelog| !changelog.is_empty())
+                            .unwrap_or("No changelog provided")
+                    )?;
+                }
+            }
+            Err(err) => writeln!(section, "\nCould not fetch the changelog: {err}")?,
+        }
+        sections.push((change.new.name, section));
+    }
+    sections.sort_unstable_by_key(|(name, _)| name.to_lowercase());
+    Ok(sections
+        .into_iter()
+        .map(|(_, section)| section)
+        .display("\n")
+        .to_string())
+}
+
+/// Write `changelog` to `output` if provided, or show it in the user's pager
+///
+/// The changelog is printed instead if standard output is not a terminal or the pager cannot be started.
+pub fn show(changelog: &str, output: Option<&Path>) -> Result<()> {
+    if let Some(output) = output {
+        write(output, changelog)?;
+        return Ok(());
+    }
+    if !std::io::stdout().is_terminal() {
+        println!("\n{changelog}");
+        return Ok(());
+    }
+
+    let pager = var("PAGER").unwrap_or_else(|_| "less -R".to_owned());
+    let mut pager = pager.split_whitespace();
+    let Some(mut child) = pager.next().and_then(|program| {
+        Command::new(program)
+            .args(pager)
+            .stdin(Stdio::piped())
+            .spawn()
+            .ok()
+    }) else {
+        println!("\n{changelog}");
+        return Ok(());
+    };
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("Could not write to the pager"))?
+        .write_all(changelog.as_bytes())?;
+    child.wait()?;
+    Ok(())
+}
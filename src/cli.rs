@@ -1,5 +1,6 @@
 #![deny(missing_docs)]
 
+use crate::settings::{OptionalDependencies, Side};
 use clap::{Args, Parser, Subcommand, ValueEnum, ValueHint};
 use clap_complete::Shell;
 use libium::config::{
@@ -21,6 +22,26 @@ pub struct Ferium {
     /// Specify the maximum number of parallel network requests to perform.
     #[clap(long, short = 'p')]
     pub parallel_network: Option<usize>,
+    /// The number of times to retry a network request that failed because of a rate limit or a temporary error.
+    /// Defaults to 3.
+    #[clap(long)]
+    pub retries: Option<u32>,
+    /// The delay in milliseconds before retrying a failed network request, which doubles after every retry.
+    /// Defaults to 1000.
+    #[clap(long)]
+    pub retry_delay: Option<u64>,
+    /// The longest delay in seconds before retrying a failed network request,
+    /// including delays requested by the server. Defaults to 60.
+    #[clap(long)]
+    pub max_retry_delay: Option<u64>,
+    /// Upgrade profiles and modpacks using only the files resolved and downloaded by previous upgrades,
+    /// without accessing the internet. Fails for any file that has never been downloaded.
+    #[clap(long)]
+    pub offline: bool,
+    /// Never prompt for input, which is also the case when stdin is not a terminal.
+    /// Prompts use their default instead, or fail with an error naming the argument to provide.
+    #[clap(long, visible_alias = "yes")]
+    pub no_input: bool,
     /// Set a GitHub personal access token for increasing the GitHub API rate limit.
     /// You can also use the environment variable `GITHUB_TOKEN`.
     #[clap(long, visible_alias = "gh")]
@@ -51,6 +72,7 @@ pub enum SubCommands {
         /// You can also use the project slug in the URL.
         /// The Curseforge project ID is specified at the top of the right sidebar under 'About Project'.
         /// The GitHub identifier is the repository's full name, e.g. `gorilla-devs/ferium`.
+        /// Use `ferium search` to find and add mods without looking up their IDs.
         #[clap(required = true)]
         identifiers: Vec<String>,
 
@@ -78,6 +100,11 @@ pub enum SubCommands {
         #[clap(long, short, visible_alias = "override")]
         force: bool,
     },
+    /// Show or prune the cache of downloaded files shared by all profiles and modpacks
+    Cache {
+        #[clap(subcommand)]
+        subcommand: CacheSubCommands,
+    },
     /// Print shell auto completions for the specified shell
     Complete {
         /// The shell to generate auto completions for
@@ -96,7 +123,17 @@ pub enum SubCommands {
         /// Complements the verbose flag.
         #[clap(long, short, visible_alias = "md")]
         markdown: bool,
+        /// Fetch the metadata again instead of using the metadata cached in the last day
+        #[clap(long, requires = "verbose")]
+        refresh: bool,
+        /// The format to output the mods in.
+        /// The JSON output includes the identifier and platform of each mod, and their metadata if verbose.
+        #[clap(long, value_enum, default_value_t, conflicts_with = "markdown")]
+        format: ListFormat,
     },
+    /// Resolve the latest compatible version of your mods and record them in the lockfile without downloading them.
+    /// The lockfile is stored next to the config file.
+    Lock,
     /// Add, configure, delete, switch, list, or upgrade modpacks
     Modpack {
         #[clap(subcommand)]
@@ -104,6 +141,17 @@ pub enum SubCommands {
     },
     /// List all the modpacks with their data
     Modpacks,
+    /// Check which mods have updates available without changing the output directory.
+    /// Exits with code 2 if there are updates.
+    Outdated,
+    /// Pin a mod to a specific version, so that upgrading doesn't change it.
+    /// If the version is not provided, pick one from the versions compatible with the profile.
+    Pin {
+        /// The project ID or case-insensitive name of the mod to pin
+        mod_name: String,
+        /// The Modrinth version ID or number, CurseForge file ID, or GitHub release tag to pin the mod to
+        version: Option<String>,
+    },
     /// Create, configure, delete, switch, or list profiles
     Profile {
         #[clap(subcommand)]
@@ -118,14 +166,66 @@ pub enum SubCommands {
         /// List of project IDs or case-insensitive names of mods to remove
         mod_names: Vec<String>,
     },
+    /// Restore the mods in the profile's output directory from before an upgrade.
+    /// A generation is saved in the `.old` folder every time an upgrade changes the output directory.
+    Rollback {
+        /// The generation to restore, defaults to the latest generation.
+        /// Rolling back also saves a generation, so running this again undoes the rollback.
+        #[clap(long)]
+        to: Option<usize>,
+        /// List the available generations instead of restoring one
+        #[clap(long, short, conflicts_with_all = ["to", "prune"])]
+        list: bool,
+        /// Delete all but the latest given number of generations instead of restoring one
+        #[clap(long, conflicts_with = "to")]
+        prune: Option<usize>,
+    },
+    /// Search Modrinth and CurseForge for mods compatible with the profile, and pick the ones to add
+    Search {
+        /// The text to search for
+        #[clap(required = true)]
+        query: Vec<String>,
+        /// Only search this platform instead of both
+        #[clap(long, short)]
+        platform: Option<Platform>,
+        /// The maximum number of results to show from each platform
+        #[clap(long, short, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Show the mods in the profile along with the dependencies they require
+    Tree,
+    /// Unpin a mod, so that it is upgraded to the latest compatible version again
+    Unpin {
+        /// The project ID or case-insensitive name of the mod to unpin
+        mod_name: String,
+    },
     /// Download and install the latest compatible version of your mods
     #[clap(visible_aliases = ["download", "install"])]
-    Upgrade,
+    Upgrade {
+        /// Install the exact files recorded in the lockfile instead of resolving the latest versions
+        #[clap(long)]
+        locked: bool,
+        /// Print what would be downloaded, moved to `.old`, deleted, and installed without changing anything
+        #[clap(long)]
+        dry_run: bool,
+        /// Show the changelogs of the mods being updated in a pager.
+        /// Optionally, provide a file to write them to instead.
+        #[clap(long, conflicts_with = "locked")]
+        #[clap(value_hint(ValueHint::FilePath))]
+        #[expect(clippy::option_option)]
+        changelog: Option<Option<PathBuf>>,
+        /// Write a JSON report of the resolved files, failures, added dependencies,
+        /// files moved to `.old`, downloads, and installed `user` mods to this file
+        #[clap(long)]
+        #[clap(value_hint(ValueHint::FilePath))]
+        report: Option<PathBuf>,
+    },
 }
 
 #[derive(Subcommand)]
 pub enum ProfileSubCommands {
-    /// Configure the current profile's name, Minecraft version, mod loader, and output directory.
+    /// Configure the current profile's name, Minecraft version, mod loader, output directory, side,
+    /// and optional dependencies.
     /// Optionally, provide the settings to change as arguments.
     #[clap(visible_aliases = ["config", "conf"])]
     Configure {
@@ -143,6 +243,13 @@ pub enum ProfileSubCommands {
         #[clap(long, short)]
         #[clap(value_hint(ValueHint::DirPath))]
         output_dir: Option<PathBuf>,
+        /// Whether the mods are for a client or a dedicated server.
+        /// Mods that don't support servers are skipped, along with their dependencies, when set to server.
+        #[clap(long, value_enum)]
+        side: Option<Side>,
+        /// Which optional dependencies of the mods to install
+        #[clap(long, value_enum)]
+        optional_dependencies: Option<OptionalDependencies>,
     },
     /// Create a new profile.
     /// Optionally, provide the settings as arguments.
@@ -168,6 +275,13 @@ pub enum ProfileSubCommands {
         #[clap(long, short)]
         #[clap(value_hint(ValueHint::DirPath))]
         output_dir: Option<PathBuf>,
+        /// Whether the mods are for a client or a dedicated server.
+        /// Mods that don't support servers are skipped, along with their dependencies, when set to server.
+        #[clap(long, value_enum)]
+        side: Option<Side>,
+        /// Which optional dependencies of the mods to install
+        #[clap(long, value_enum)]
+        optional_dependencies: Option<OptionalDependencies>,
     },
     /// Delete a profile.
     /// Optionally, provide the name of the profile to delete.
@@ -179,6 +293,29 @@ pub enum ProfileSubCommands {
         #[clap(long, short)]
         switch_to: Option<String>,
     },
+    /// Export the current profile as a modpack.
+    /// The latest compatible versions of the mods are resolved and downloaded to do so.
+    Export {
+        /// The modpack format to export to
+        #[clap(long, short, value_enum, default_value_t)]
+        format: ExportFormat,
+        /// The file to write the modpack to.
+        /// Defaults to the profile's name in the current directory.
+        #[clap(long, short)]
+        #[clap(value_hint(ValueHint::FilePath))]
+        output: Option<PathBuf>,
+        /// The version of the exported modpack
+        #[clap(long, default_value = "1.0.0")]
+        pack_version: String,
+        /// The version of the mod loader to use.
+        /// Defaults to the latest version for Fabric and Quilt, and has to be provided for Forge and NeoForge.
+        #[clap(long)]
+        loader_version: Option<String>,
+        /// A directory whose contents are included in the modpack as overrides, e.g. configs
+        #[clap(long)]
+        #[clap(value_hint(ValueHint::DirPath))]
+        overrides: Option<PathBuf>,
+    },
     /// Show information about the current profile
     Info,
     /// List all the profiles with their data
@@ -200,6 +337,8 @@ pub enum ModpackSubCommands {
         /// The Modrinth project ID is specified at the bottom of the left sidebar under 'Technical information'.
         /// You can also use the project slug for this.
         /// The Curseforge project ID is specified at the top of the right sidebar under 'About Project'.
+        /// You can also use the path to a local `.mrpack` or CurseForge modpack zip,
+        /// which is read again every time the modpack is upgraded.
         identifier: String,
         /// The Minecraft instance directory to install the modpack to
         #[clap(long, short)]
@@ -210,7 +349,7 @@ pub enum ModpackSubCommands {
         #[clap(long, short)]
         install_overrides: Option<bool>,
     },
-    /// Configure the current modpack's output directory and installation of overrides.
+    /// Configure the current modpack's output directory, installation of overrides, and side.
     /// Optionally, provide the settings to change as arguments.
     #[clap(visible_aliases = ["config", "conf"])]
     Configure {
@@ -222,6 +361,10 @@ pub enum ModpackSubCommands {
         /// This will override existing files when upgrading.
         #[clap(long, short)]
         install_overrides: Option<bool>,
+        /// Whether the modpack is installed for a client or a dedicated server.
+        /// Files that don't support servers are skipped when set to server.
+        #[clap(long, value_enum)]
+        side: Option<Side>,
     },
     /// Delete a modpack.
     /// Optionally, provide the name of the modpack to delete.
@@ -245,7 +388,35 @@ pub enum ModpackSubCommands {
     },
     /// Download and install the latest version of the modpack
     #[clap(visible_aliases = ["download", "install"])]
-    Upgrade,
+    Upgrade {
+        /// Print what would be downloaded, moved to `.old`, deleted, and installed without changing anything
+        #[clap(long)]
+        dry_run: bool,
+        /// Also write a Prism Launcher/MultiMC instance (`instance.cfg` and `mmc-pack.json`) for the modpack.
+        ///
+        /// The modpack's output directory has to be the `.minecraft` folder inside the instance folder.
+        #[clap(long, conflicts_with = "dry_run")]
+        instance: bool,
+        /// Write a JSON report of the modpack's files, the ones that could not be downloaded,
+        /// files moved to `.old`, downloads, and installed overrides to this file
+        #[clap(long)]
+        #[clap(value_hint(ValueHint::FilePath))]
+        report: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CacheSubCommands {
+    /// Delete the cached files that have not been downloaded or installed recently.
+    /// Files already installed to output directories are not affected.
+    Prune {
+        /// The number of days a file has to be unused for to be deleted.
+        /// Use 0 to clear the cache.
+        #[clap(long, default_value_t = crate::cache::DEFAULT_MAX_AGE_DAYS)]
+        older_than: u64,
+    },
+    /// Show the location, number of files, and size of the cache
+    Stats,
 }
 
 #[derive(Args)]
@@ -318,6 +489,34 @@ pub enum Platform {
     Curseforge,
 }
 
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ListFormat {
+    /// Coloured text, or markdown with `--markdown`
+    #[default]
+    Text,
+    /// A JSON document, which includes the metadata of the mods with `--verbose`
+    Json,
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum ExportFormat {
+    #[default]
+    #[clap(alias = "modrinth")]
+    Mrpack,
+    #[clap(alias = "cf")]
+    Curseforge,
+}
+
+impl ExportFormat {
+    /// The file extension used by this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Mrpack => "mrpack",
+            Self::Curseforge => "zip",
+        }
+    }
+}
+
 impl std::fmt::Display for Platform {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -0,0 +1,154 @@
+use crate::{lockfile::LockedFile, retry::retry_request};
+use anyhow::{bail, Result};
+use ferinth::structures::version::DependencyType;
+use furse::structures::file_structs::FileRelationType;
+use libium::{
+    config::structs::ModIdentifier, iter_ext::IterExt as _, CURSEFORGE_API, MODRINTH_API,
+};
+use std::collections::HashMap;
+
+/// A resolved mod whose author declared it incompatible with another resolved mod
+struct Conflict<'a> {
+    declared_by: &'a str,
+    with: &'a str,
+    platform: &'static str,
+}
+
+impl std::fmt::Display for Conflict<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "  {} declares that it is incompatible with {} on {}",
+            self.declared_by, self.with, self.platform
+        )
+    }
+}
+
+/// Check that none of the `locked` files declare themselves incompatible with another locked file
+///
+/// This uses Modrinth's `incompatible` dependencies and CurseForge's incompatible relations,
+/// with a maximum of 3 network requests.
+/// CurseForge files that were also uploaded to Modrinth are looked up using their hash,
+/// so that Modrinth's incompatibilities apply to them too. CurseForge's incompatibilities only apply to CurseForge files,
+/// as other files can't be looked up on CurseForge.
+/// Fails with a list of the conflicting mods if there are any.
+pub async fn check(locked: &[LockedFile]) -> Result<()> {
+    let cf_hashes = locked
+        .iter()
+        .filter(|file| matches!(file.project, ModIdentifier::CurseForgeProject(_)))
+        .filter_map(|file| file.sha1.clone())
+        .collect_vec();
+    // The Modrinth project and version that CurseForge files were also uploaded as, keyed by the files' hashes
+    let mr_versions: HashMap<String, (String, String)> = if cf_hashes.is_empty() {
+        HashMap::new()
+    } else {
+        retry_request(|| MODRINTH_API.get_versions_from_hashes(cf_hashes.clone()))
+            .await?
+            .into_iter()
+            .map(|(hash, version)| (hash, (version.project_id, version.id)))
+            .collect()
+    };
+    // The Modrinth project and version ID of `file`, if it is known
+    let modrinth = |file: &LockedFile| match &file.project {
+        ModIdentifier::ModrinthProject(id) | ModIdentifier::PinnedModrinthProject(id, _) => {
+            Some((id.clone(), file.file_id.clone()))
+        }
+        ModIdentifier::CurseForgeProject(_) => file
+            .sha1
+            .as_ref()
+            .and_then(|hash| mr_versions.get(hash))
+            .cloned(),
+        _ => None,
+    };
+    // The name of the locked file that matches a declared incompatibility, other than the declaring file itself
+    let find = |declared_by: usize, matches: &dyn Fn(&LockedFile) -> bool| {
+        locked
+            .iter()
+            .enumerate()
+            .find(|(i, file)| *i != declared_by && matches(file))
+            .map(|(_, file)| file.name.as_str())
+    };
+    let mut conflicts = Vec::new();
+
+    let mr_ids = locked
+        .iter()
+        .filter(|file| {
+            matches!(
+                file.project,
+                ModIdentifier::ModrinthProject(_) | ModIdentifier::PinnedModrinthProject(..)
+            )
+        })
+        .map(|file| file.file_id.as_str())
+        .collect_vec();
+    if !mr_ids.is_empty() {
+        for version in retry_request(|| MODRINTH_API.get_multiple_versions(&mr_ids)).await? {
+            let Some(declared_by) = locked.iter().position(|file| {
+                modrinth(file).is_some_and(|(_, version_id)| version_id == version.id)
+            }) else {
+                continue;
+            };
+            for dependency in version
+                .dependencies
+                .iter()
+                .filter(|dep| matches!(dep.dependency_type, DependencyType::Incompatible))
+            {
+                let with = find(declared_by, &|file| {
+                    modrinth(file).is_some_and(|(project_id, version_id)| {
+                        match &dependency.version_id {
+                            // Only the specified version is incompatible
+                            Some(incompatible) => *incompatible == version_id,
+                            None => dependency.project_id.as_ref() == Some(&project_id),
+                        }
+                    })
+                });
+                if let Some(with) = with {
+                    conflicts.push(Conflict {
+                        declared_by: &locked[declared_by].name,
+                        with,
+                        platform: "Modrinth",
+                    });
+                }
+            }
+        }
+    }
+
+    let cf_ids = locked
+        .iter()
+        .filter(|file| matches!(file.project, ModIdentifier::CurseForgeProject(_)))
+        .filter_map(|file| file.file_id.parse().ok())
+        .collect_vec();
+    if !cf_ids.is_empty() {
+        for file in retry_request(|| CURSEFORGE_API.get_files(cf_ids.clone())).await? {
+            let Some(declared_by) = locked.iter().position(|locked_file| {
+                matches!(locked_file.project, ModIdentifier::CurseForgeProject(_))
+                    && locked_file.file_id == file.id.to_string()
+            }) else {
+                continue;
+            };
+            for dependency in file
+                .dependencies
+                .iter()
+                .filter(|dep| matches!(dep.relation_type, FileRelationType::Incompatible))
+            {
+                let with = find(declared_by, &|locked_file| {
+                    locked_file.project == ModIdentifier::CurseForgeProject(dependency.mod_id)
+                });
+                if let Some(with) = with {
+                    conflicts.push(Conflict {
+                        declared_by: &locked[declared_by].name,
+                        with,
+                        platform: "CurseForge",
+                    });
+                }
+            }
+        }
+    }
+
+    if !conflicts.is_empty() {
+        bail!(
+            "\nSome mods are incompatible with each other, remove one of each pair to continue\n{}",
+            conflicts.iter().display("\n")
+        );
+    }
+    Ok(())
+}
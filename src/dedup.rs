@@ -0,0 +1,76 @@
+use crate::{
+    lockfile::{platform, LockedFile},
+    retry::retry_request,
+};
+use anyhow::Result;
+use colored::Colorize as _;
+use libium::{
+    config::structs::{ModIdentifier, Profile},
+    iter_ext::IterExt as _,
+    MODRINTH_API,
+};
+use std::collections::HashMap;
+
+/// Remove the files in `locked` that are the same mod as another file from a different platform
+///
+/// Files are the same mod if their hashes match, or if a CurseForge file was also uploaded to a Modrinth project
+/// that another file belongs to, which is looked up using its hash in one network request.
+/// The files of mods configured in `profile` are preferred over the ones pulled in as dependencies.
+pub async fn deduplicate(profile: &Profile, locked: Vec<LockedFile>) -> Result<Vec<LockedFile>> {
+    let cf_hashes = locked
+        .iter()
+        .filter(|file| matches!(file.project, ModIdentifier::CurseForgeProject(_)))
+        .filter_map(|file| file.sha1.clone())
+        .collect_vec();
+    // The Modrinth projects that CurseForge files were also uploaded to, keyed by the files' hashes
+    let mr_projects: HashMap<String, String> = if cf_hashes.is_empty() {
+        HashMap::new()
+    } else {
+        retry_request(|| MODRINTH_API.get_versions_from_hashes(cf_hashes.clone()))
+            .await?
+            .into_iter()
+            .map(|(hash, version)| (hash, version.project_id))
+            .collect()
+    };
+    // The Modrinth project that `file` belongs to, if it is known
+    let mr_project = |file: &LockedFile| match &file.project {
+        ModIdentifier::ModrinthProject(id) | ModIdentifier::PinnedModrinthProject(id, _) => {
+            Some(id.clone())
+        }
+        ModIdentifier::CurseForgeProject(_) => file
+            .sha1
+            .as_ref()
+            .and_then(|hash| mr_projects.get(hash))
+            .cloned(),
+        _ => None,
+    };
+
+    let (mut files, dependencies): (Vec<_>, Vec<_>) = locked.into_iter().partition(|file| {
+        profile
+            .mods
+            .iter()
+            .any(|mod_| mod_.identifier == file.project)
+    });
+    files.extend(dependencies);
+
+    let mut deduplicated = Vec::<LockedFile>::new();
+    for file in files {
+        if let Some(same) = deduplicated.iter().find(|kept| {
+            platform(&kept.project) != platform(&file.project)
+                && ((kept.sha1.is_some() && kept.sha1 == file.sha1)
+                    || (mr_project(kept).is_some() && mr_project(kept) == mr_project(&file)))
+        }) {
+            println!(
+                "{} {} from {} is the same mod as {} from {}, skipping it",
+                "-".yellow(),
+                file.name.bold(),
+                platform(&file.project),
+                same.name.bold(),
+                platform(&same.project),
+            );
+        } else {
+            deduplicated.push(file);
+        }
+    }
+    Ok(deduplicated)
+}
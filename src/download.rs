@@ -1,34 +1,335 @@
 #![expect(clippy::expect_used, reason = "For mutex poisons")]
 
-use crate::{DEFAULT_PARALLEL_NETWORK, PARALLEL_NETWORK, STYLE_BYTE, TICK};
-use anyhow::{anyhow, bail, Error, Result};
-use colored::Colorize as _;
-use fs_extra::{
-    dir::{copy as copy_dir, CopyOptions as DirCopyOptions},
-    file::{move_file, CopyOptions as FileCopyOptions},
+use crate::{
+    cache, generations,
+    lockfile::{LockedFile, LockedModpackFile},
+    report::{DownloadedFile, Failure},
+    retry::{retry, RetryableStatus},
+    CROSS, DEFAULT_PARALLEL_NETWORK, PARALLEL_NETWORK, STYLE_BYTE, TICK,
 };
+use anyhow::{anyhow, bail, Context as _, Result};
+use colored::Colorize as _;
+use fs_extra::dir::{copy as copy_dir, CopyOptions as DirCopyOptions};
 use indicatif::ProgressBar;
 use libium::{iter_ext::IterExt as _, upgrade::DownloadData};
+use md5::Md5;
+use reqwest::{header::RANGE, Client, StatusCode, Url};
+use sha1::{Digest as _, Sha1};
+use sha2::Sha512;
 use std::{
     ffi::OsString,
-    fs::{copy, create_dir_all, read_dir, remove_file},
+    fs::{copy, create_dir_all, read_dir, remove_file, rename, File, OpenOptions},
+    io::{copy as copy_io, BufWriter, Write as _},
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
     time::Duration,
 };
 use tokio::{sync::Semaphore, task::JoinSet};
 
-/// Check the given `directory`
+/// The number of times a file is downloaded before giving up if its hash does not match
+pub const DOWNLOAD_ATTEMPTS: usize = 3;
+
+/// The downloaded file's hash did not match the one provided by the platform
+#[derive(Debug)]
+pub struct HashMismatchError {
+    /// The name of the hash algorithm, e.g. `SHA-1`
+    pub algorithm: &'static str,
+    pub filename: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl std::fmt::Display for HashMismatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The {} hash of {} was {}, but {} was expected",
+            self.algorithm, self.filename, self.actual, self.expected
+        )
+    }
+}
+
+impl std::error::Error for HashMismatchError {}
+
+/// Computes the hash of a downloaded file to verify it with
+enum Hasher {
+    Sha1(Sha1),
+    Sha512(Sha512),
+    Md5(Md5),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha1(hasher) => hasher.update(data),
+            Self::Sha512(hasher) => hasher.update(data),
+            Self::Md5(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            Self::Sha1(hasher) => format!("{:x}", hasher.finalize()),
+            Self::Sha512(hasher) => format!("{:x}", hasher.finalize()),
+            Self::Md5(hasher) => format!("{:x}", hasher.finalize()),
+        }
+    }
+}
+
+impl std::io::Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A file to download, independent of the platform it was resolved from
+#[derive(Debug, Clone)]
+pub struct Downloadable {
+    pub url: Url,
+    /// The path of the downloaded file relative to the output directory
+    pub output: PathBuf,
+    /// The length of the file in bytes
+    pub length: usize,
+    /// The SHA-1 hash to verify the downloaded file with, if the platform provides one
+    pub sha1: Option<String>,
+    /// The SHA-512 hash provided by Modrinth, used if there is no SHA-1 hash
+    pub sha512: Option<String>,
+    /// The MD5 hash provided by CurseForge, used if there is no SHA-1 hash
+    pub md5: Option<String>,
+}
+
+/// [`DownloadData`] doesn't include the file's hashes,
+/// so they have to be set from the platform's file, e.g. using [`lockfile::lock`](crate::lockfile::lock)
+impl From<DownloadData> for Downloadable {
+    fn from(value: DownloadData) -> Self {
+        Self {
+            url: value.download_url,
+            output: value.output,
+            length: value.length,
+            sha1: None,
+            sha512: None,
+            md5: None,
+        }
+    }
+}
+
+impl From<LockedFile> for Downloadable {
+    fn from(value: LockedFile) -> Self {
+        Self {
+            url: value.url,
+            output: value.filename.into(),
+            length: value.size,
+            sha1: value.sha1,
+            sha512: value.sha512,
+            md5: value.md5,
+        }
+    }
+}
+
+impl From<LockedModpackFile> for Downloadable {
+    fn from(value: LockedModpackFile) -> Self {
+        Self {
+            url: value.url,
+            output: value.output,
+            length: value.size,
+            sha1: value.sha1,
+            sha512: value.sha512,
+            md5: value.md5,
+        }
+    }
+}
+
+impl From<Downloadable> for LockedModpackFile {
+    fn from(value: Downloadable) -> Self {
+        Self {
+            url: value.url,
+            output: value.output,
+            size: value.length,
+            sha1: value.sha1,
+            sha512: value.sha512,
+            md5: value.md5,
+        }
+    }
+}
+
+impl Downloadable {
+    pub fn filename(&self) -> String {
+        self.output
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Get the name of the algorithm, the expected hash, and a hasher to verify the downloaded file with
+    ///
+    /// SHA-1 is preferred as the download cache is keyed by it, the other hashes are used if the platform doesn't provide it.
+    fn verifier(&self) -> Option<(&'static str, &str, Hasher)> {
+        if let Some(sha1) = &self.sha1 {
+            Some(("SHA-1", sha1, Hasher::Sha1(Sha1::new())))
+        } else if let Some(sha512) = &self.sha512 {
+            Some(("SHA-512", sha512, Hasher::Sha512(Sha512::new())))
+        } else {
+            self.md5
+                .as_ref()
+                .map(|md5| ("MD5", md5.as_str(), Hasher::Md5(Md5::new())))
+        }
+    }
+
+    /// Get the path of the partial download of this file in `output_dir`
+    pub fn part_path(&self, output_dir: &Path) -> PathBuf {
+        let mut path = output_dir.join(&self.output).into_os_string();
+        path.push(".part");
+        path.into()
+    }
+
+    /// Download this file to `output_dir`, calling `update` with the number of bytes written after every chunk
+    ///
+    /// The file is first written to a `.part` file, which is renamed once the download completes
+    /// and its hash has been verified. If the hash does not match, the `.part` file is deleted
+    /// and a [`HashMismatchError`] is returned.
+    /// If a `.part` file is already there, the download is resumed from where it left off using a range request,
+    /// or started over if the server does not support them.
+    /// Returns the size of the file and the filename.
+    pub async fn download(
+        &self,
+        client: Client,
+        output_dir: &Path,
+        update: impl Fn(usize) + Send,
+    ) -> Result<(usize, String)> {
+        let out_file_path = output_dir.join(&self.output);
+        let part_file_path = self.part_path(output_dir);
+        if let Some(parent) = out_file_path.parent() {
+            create_dir_all(parent)?;
+        }
+
+        let mut offset = part_file_path
+            .metadata()
+            .map_or(0, |metadata| metadata.len());
+        let mut request = client.get(self.url.clone());
+        if offset > 0 {
+            request = request.header(RANGE, format!("bytes={offset}-"));
+        }
+        let response = request.send().await?;
+        if let Some(err) = RetryableStatus::from_response(response.status(), response.headers()) {
+            bail!(err);
+        }
+        // The server has no more bytes to send if the partial download is already complete
+        let response = if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            None
+        } else {
+            Some(response.error_for_status()?)
+        };
+
+        let mut verifier = self.verifier();
+        let part_file = if offset > 0
+            && response.as_ref().map_or(true, |response| {
+                response.status() == StatusCode::PARTIAL_CONTENT
+            }) {
+            if let Some((_, _, hasher)) = &mut verifier {
+                copy_io(&mut File::open(&part_file_path)?, hasher)?;
+            }
+            update(usize::try_from(offset)?);
+            OpenOptions::new().append(true).open(&part_file_path)?
+        } else {
+            // Start from the beginning if there is nothing to resume or the server sent the whole file
+            offset = 0;
+            File::create(&part_file_path)?
+        };
+        let mut part_file = BufWriter::new(part_file);
+        let mut length = usize::try_from(offset)?;
+        if let Some(mut response) = response {
+            while let Some(chunk) = response.chunk().await? {
+                part_file.write_all(&chunk)?;
+                if let Some((_, _, hasher)) = &mut verifier {
+                    hasher.update(&chunk);
+                }
+                length += chunk.len();
+                update(chunk.len());
+            }
+        }
+        part_file
+            .into_inner()
+            .context("Could not flush the downloaded file")?
+            .sync_all()?;
+
+        if let Some((algorithm, expected, hasher)) = verifier {
+            let actual = hasher.finalize();
+            if !actual.eq_ignore_ascii_case(expected) {
+                remove_file(part_file_path)?;
+                bail!(HashMismatchError {
+                    algorithm,
+                    filename: self.filename(),
+                    expected: expected.to_owned(),
+                    actual,
+                });
+            }
+        }
+        rename(part_file_path, out_file_path)?;
+
+        Ok((length, self.filename()))
+    }
+}
+
+/// The changes [`clean`] will make to a directory
+#[derive(Debug, Default)]
+pub struct CleanPlan {
+    /// Files that are already up to date and will be kept
+    pub to_keep: Vec<PathBuf>,
+    /// Files that will be moved to a new generation in `directory`/.old
+    pub to_move: Vec<PathBuf>,
+    /// `.part` files that will be deleted
+    pub to_delete: Vec<PathBuf>,
+}
+
+impl CleanPlan {
+    pub fn is_empty(&self) -> bool {
+        self.to_move.is_empty() && self.to_delete.is_empty()
+    }
+
+    /// Print the changes this plan would make, without making them
+    pub fn print(&self) {
+        for path in &self.to_move {
+            println!(
+                "{} Would move to .old  {}",
+                "-".yellow(),
+                path.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .dimmed()
+            );
+        }
+        for path in &self.to_delete {
+            println!(
+                "{} Would delete        {}",
+                CROSS.red(),
+                path.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .dimmed()
+            );
+        }
+    }
+}
+
+/// Check the given `directory` without changing anything in it
 ///
-/// - If there are files there that are not in `to_download` or `to_install`, they will be moved to `directory`/.old
+/// - If there are files there that are not in `to_download` or `to_install`, they are planned to be moved to `directory`/.old
 /// - If a file in `to_download` or `to_install` is already there, it will be removed from the respective vector
-/// - If the file is a `.part` file or if the move failed, the file will be deleted
-pub async fn clean(
+/// - If the file is a `.part` file, it is planned to be deleted unless it is the partial download of a file in `to_download`
+pub fn plan_clean(
     directory: &Path,
-    to_download: &mut Vec<DownloadData>,
+    to_download: &mut Vec<Downloadable>,
     to_install: &mut Vec<(OsString, PathBuf)>,
-) -> Result<()> {
-    let dupes = find_dupes_by_key(to_download, DownloadData::filename);
+) -> Result<CleanPlan> {
+    let dupes = find_dupes_by_key(to_download, Downloadable::filename);
     if !dupes.is_empty() {
         println!(
             "{}",
@@ -44,7 +345,11 @@ pub async fn clean(
             .bold()
         );
     }
-    create_dir_all(directory.join(".old"))?;
+    let mut plan = CleanPlan::default();
+    if !directory.exists() {
+        return Ok(plan);
+    }
+    let mut parts = Vec::new();
     for file in read_dir(directory)? {
         let file = file?;
         // If it's a file
@@ -59,25 +364,72 @@ pub async fn clean(
             {
                 // Don't download it
                 to_download.swap_remove(index);
+                plan.to_keep.push(file.path());
             // Likewise, if it is already installed
             } else if let Some(index) = to_install.iter().position(|thing| filename == thing.0) {
                 // Don't install it
                 to_install.swap_remove(index);
-            // Or else, move the file to `directory`/.old
-            // If the file is a `.part` file or if the move failed, delete the file
-            } else if filename.ends_with("part")
-                || move_file(
-                    file.path(),
-                    directory.join(".old").join(filename),
-                    &FileCopyOptions::new(),
-                )
-                .is_err()
-            {
-                remove_file(file.path())?;
+                plan.to_keep.push(file.path());
+            // Or else, check whether it's a `.part` file once the files to download are known
+            } else if let Some(name) = filename.strip_suffix(".part") {
+                parts.push((name.to_owned(), file.path()));
+            // and move it to `directory`/.old otherwise
+            } else {
+                plan.to_move.push(file.path());
             }
         }
     }
-    Ok(())
+    // Delete the `.part` files that won't be resumed
+    for (name, path) in parts {
+        if !to_download.iter().any(|thing| name == thing.filename()) {
+            plan.to_delete.push(path);
+        }
+    }
+    Ok(plan)
+}
+
+/// Check the given `directory` and carry out the [`plan_clean`] for it
+///
+/// If anything in `directory` is going to change, the files currently in it are saved as a new generation in `directory`/.old,
+/// which can be restored using `ferium rollback`. Files that are not in `to_download` or `to_install` are moved into this generation.
+/// Returns the plan that was carried out.
+pub async fn clean(
+    directory: &Path,
+    to_download: &mut Vec<Downloadable>,
+    to_install: &mut Vec<(OsString, PathBuf)>,
+) -> Result<CleanPlan> {
+    create_dir_all(directory.join(".old"))?;
+    let plan = plan_clean(directory, to_download, to_install)?;
+    for path in &plan.to_delete {
+        remove_file(path)?;
+    }
+    if !plan.to_move.is_empty() || !to_download.is_empty() || !to_install.is_empty() {
+        generations::snapshot(directory, &plan.to_keep, &plan.to_move)?;
+        generations::prune(directory, generations::DEFAULT_RETENTION)?;
+    }
+    Ok(plan)
+}
+
+/// Print the files in `to_download` and `to_install` without downloading or installing them
+pub fn print_downloads(to_download: &[Downloadable], to_install: &[(OsString, PathBuf)]) {
+    for downloadable in to_download {
+        println!(
+            "{} Would download  {:>7}  {}",
+            "+".green(),
+            size::Size::from_bytes(downloadable.length)
+                .format()
+                .with_base(size::Base::Base10)
+                .to_string(),
+            downloadable.filename().dimmed(),
+        );
+    }
+    for (name, _) in to_install {
+        println!(
+            "{} Would install            {}",
+            "+".green(),
+            name.to_string_lossy().dimmed()
+        );
+    }
 }
 
 /// Construct a `to_install` vector from the `directory`
@@ -93,11 +445,15 @@ pub fn read_overrides(directory: &Path) -> Result<Vec<(OsString, PathBuf)>> {
 }
 
 /// Download and install the files in `to_download` and `to_install` to `output_dir`
+///
+/// Files with a known hash are installed from the [`cache`] if they are in it, and added to it once downloaded.
+/// A file that fails to download doesn't stop the others from being downloaded.
+/// Returns the files that were downloaded or installed from the cache, and the files that could not be downloaded.
 pub async fn download(
     output_dir: PathBuf,
-    to_download: Vec<DownloadData>,
+    to_download: Vec<Downloadable>,
     to_install: Vec<(OsString, PathBuf)>,
-) -> Result<()> {
+) -> Result<(Vec<DownloadedFile>, Vec<Failure>)> {
     let progress_bar = Arc::new(Mutex::new(
         ProgressBar::new(
             to_download
@@ -124,33 +480,28 @@ pub async fn download(
         let output_dir = output_dir.clone();
 
         tasks.spawn(async move {
-            let _permit = semaphore.acquire_owned().await?;
-
-            let (length, filename) = downloadable
-                .download(client, &output_dir, |additional| {
+            let name = downloadable.filename();
+            download_file(downloadable, client, &output_dir, &semaphore, &progress_bar)
+                .await
+                .map_err(|err| {
                     progress_bar
                         .lock()
                         .expect("Mutex poisoned")
-                        .inc(additional as u64);
+                        .println(format!("{CROSS} {name}  {err:#}").red().to_string());
+                    Failure {
+                        name,
+                        error: format!("{err:#}"),
+                    }
                 })
-                .await?;
-            progress_bar
-                .lock()
-                .expect("Mutex poisoned")
-                .println(format!(
-                    "{} Downloaded  {:>7}  {}",
-                    &*TICK,
-                    size::Size::from_bytes(length)
-                        .format()
-                        .with_base(size::Base::Base10)
-                        .to_string(),
-                    filename.dimmed(),
-                ));
-            Ok::<(), Error>(())
         });
     }
+    let mut downloaded = Vec::new();
+    let mut failures = Vec::new();
     for res in tasks.join_all().await {
-        res?;
+        match res {
+            Ok(file) => downloaded.push(file),
+            Err(failure) => failures.push(failure),
+        }
     }
     Arc::try_unwrap(progress_bar)
         .map_err(|_| anyhow!("Failed to run threads to completion"))?
@@ -173,7 +524,118 @@ pub async fn download(
         );
     }
 
-    Ok(())
+    Ok((downloaded, failures))
+}
+
+/// Download `downloadable` to `output_dir`, or install it from the [`cache`] if it is there
+async fn download_file(
+    downloadable: Downloadable,
+    client: Client,
+    output_dir: &Path,
+    semaphore: &Semaphore,
+    progress_bar: &Mutex<ProgressBar>,
+) -> Result<DownloadedFile> {
+    let out_file_path = output_dir.join(&downloadable.output);
+    if let Some(sha1) = &downloadable.sha1 {
+        if cache::install(sha1, &out_file_path)? {
+            let progress_bar = progress_bar.lock().expect("Mutex poisoned");
+            progress_bar.inc(downloadable.length as u64);
+            progress_bar.println(format!(
+                "{} From cache  {:>7}  {}",
+                &*TICK,
+                size::Size::from_bytes(downloadable.length)
+                    .format()
+                    .with_base(size::Base::Base10)
+                    .to_string(),
+                downloadable.filename().dimmed(),
+            ));
+            return Ok(DownloadedFile {
+                filename: downloadable.filename(),
+                size: downloadable.length,
+                from_cache: true,
+            });
+        }
+    }
+    let _permit = semaphore.acquire().await?;
+
+    let mut attempt = 1;
+    // The number of bytes downloaded in the current request
+    let downloaded = AtomicUsize::new(0);
+    let (length, filename) = loop {
+        match retry(
+            || {
+                downloaded.store(0, Ordering::Relaxed);
+                downloadable.download(client.clone(), output_dir, |additional| {
+                    downloaded.fetch_add(additional, Ordering::Relaxed);
+                    progress_bar
+                        .lock()
+                        .expect("Mutex poisoned")
+                        .inc(additional as u64);
+                })
+            },
+            |err, delay| {
+                let progress_bar = progress_bar.lock().expect("Mutex poisoned");
+                progress_bar.println(format!(
+                    "{} {err}, retrying {} in {:.1}s",
+                    "Warning:".yellow().bold(),
+                    downloadable.filename(),
+                    delay.as_secs_f64()
+                ));
+                // The discarded bytes will be downloaded again
+                progress_bar.inc_length(downloaded.load(Ordering::Relaxed) as u64);
+            },
+        )
+        .await
+        {
+            Err(err) if err.downcast_ref::<HashMismatchError>().is_some() => {
+                if attempt == DOWNLOAD_ATTEMPTS {
+                    bail!(
+                        "{err}\nGave up on {} after {DOWNLOAD_ATTEMPTS} attempts",
+                        downloadable.filename()
+                    );
+                }
+                let progress_bar = progress_bar.lock().expect("Mutex poisoned");
+                progress_bar.println(format!(
+                    "{} {err}, retrying ({attempt}/{DOWNLOAD_ATTEMPTS})",
+                    "Warning:".yellow().bold(),
+                ));
+                // The discarded bytes will be downloaded again
+                progress_bar.inc_length(downloadable.length as u64);
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+            Ok(downloaded) => break downloaded,
+        }
+    };
+    if let Some(sha1) = &downloadable.sha1 {
+        if let Err(err) = cache::insert(sha1, &out_file_path) {
+            progress_bar
+                .lock()
+                .expect("Mutex poisoned")
+                .println(format!(
+                    "{} Could not cache {}: {err}",
+                    "Warning:".yellow().bold(),
+                    filename
+                ));
+        }
+    }
+    progress_bar
+        .lock()
+        .expect("Mutex poisoned")
+        .println(format!(
+            "{} Downloaded  {:>7}  {}",
+            &*TICK,
+            size::Size::from_bytes(length)
+                .format()
+                .with_base(size::Base::Base10)
+                .to_string(),
+            filename.dimmed(),
+        ));
+    Ok(DownloadedFile {
+        filename,
+        size: length,
+        from_cache: false,
+    })
 }
 
 /// Find duplicates of the items in `slice` using a value obtained by the `key` closure
@@ -1,3 +1,4 @@
+use crate::prompt::can_prompt;
 use libium::HOME;
 use std::{
     io::Result,
@@ -26,12 +27,16 @@ fn show_folder_picker(default: impl AsRef<Path>, prompt: impl Into<String>) -> O
 
 /// Picks a folder using the terminal or system file picker (depending on the feature flag `gui`)
 ///
-/// The `default` path is shown/opened at first and the `name` is what folder the user is supposed to be picking (e.g. output directory)
+/// The `default` path is shown/opened at first and the `name` is what folder the user is supposed to be picking (e.g. output directory).
+/// Nothing is picked if prompts are disabled.
 pub fn pick_folder(
     default: impl AsRef<Path>,
     prompt: impl Into<String>,
     name: impl AsRef<str>,
 ) -> Result<Option<PathBuf>> {
+    if !can_prompt() {
+        return Ok(None);
+    }
     show_folder_picker(default, prompt)
         .map(|raw_in| {
             let path = raw_in
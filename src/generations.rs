@@ -0,0 +1,145 @@
+use anyhow::{ensure, Context as _, Result};
+use fs_extra::{
+    dir::{move_dir, CopyOptions as DirCopyOptions},
+    file::{move_file, CopyOptions},
+};
+use std::{
+    fs::{
+        copy, create_dir_all, hard_link, read_dir, remove_dir, remove_dir_all, remove_file, rename,
+    },
+    path::{Path, PathBuf},
+};
+
+/// The number of generations kept in `.old` after an upgrade or rollback
+pub const DEFAULT_RETENTION: usize = 10;
+
+/// Get the path of `generation` in `directory`/.old
+pub fn path(directory: &Path, generation: usize) -> PathBuf {
+    directory.join(".old").join(generation.to_string())
+}
+
+/// Get the generations in `directory`/.old in ascending order
+///
+/// Files that were moved to `.old` before generations existed are ignored.
+pub fn list(directory: &Path) -> Result<Vec<usize>> {
+    let mut generations = Vec::new();
+    if directory.join(".old").exists() {
+        for entry in read_dir(directory.join(".old"))? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                if let Some(generation) = entry.file_name().to_str().and_then(|s| s.parse().ok()) {
+                    generations.push(generation);
+                }
+            }
+        }
+    }
+    generations.sort_unstable();
+    Ok(generations)
+}
+
+/// Hard link `file` to `target`, or copy it if hard links are not supported
+pub fn link_or_copy(file: &Path, target: &Path) -> Result<()> {
+    if hard_link(file, target).is_err() {
+        copy(file, target)?;
+    }
+    Ok(())
+}
+
+/// Create a new generation in `directory`/.old with the files in `to_keep` and `to_move`
+///
+/// The files in `to_keep` stay in `directory` and are linked into the generation,
+/// while the files in `to_move` are moved into it. If moving a file fails, it will be deleted.
+/// Returns the number of the new generation.
+pub fn snapshot(directory: &Path, to_keep: &[PathBuf], to_move: &[PathBuf]) -> Result<usize> {
+    let generation = list(directory)?.last().map_or(1, |latest| latest + 1);
+    let generation_dir = path(directory, generation);
+    create_dir_all(&generation_dir)?;
+
+    for file in to_keep {
+        link_or_copy(
+            file,
+            &generation_dir.join(file.file_name().context("Unable to get file name")?),
+        )?;
+    }
+    for file in to_move {
+        if move_file(
+            file,
+            generation_dir.join(file.file_name().context("Unable to get file name")?),
+            &CopyOptions::new(),
+        )
+        .is_err()
+        {
+            remove_file(file)?;
+        }
+    }
+    Ok(generation)
+}
+
+/// Delete all but the latest `retention` generations in `directory`/.old
+///
+/// Returns the generations that were deleted.
+pub fn prune(directory: &Path, retention: usize) -> Result<Vec<usize>> {
+    let mut generations = list(directory)?;
+    generations.truncate(generations.len().saturating_sub(retention));
+    for generation in &generations {
+        remove_dir_all(path(directory, *generation))?;
+    }
+    Ok(generations)
+}
+
+/// Move the generations in `from`/.old to `to`/.old, e.g. when a profile's output directory is changed
+///
+/// They are numbered after the generations already in `to`/.old, as they are the latest ones of the profile.
+pub fn relocate(from: &Path, to: &Path) -> Result<()> {
+    let generations = list(from)?;
+    if generations.is_empty() || from == to {
+        return Ok(());
+    }
+    create_dir_all(to.join(".old"))?;
+    let mut next = list(to)?.last().map_or(1, |latest| latest + 1);
+    for generation in generations {
+        let source = path(from, generation);
+        let target = path(to, next);
+        if rename(&source, &target).is_err() {
+            move_dir(
+                &source,
+                &target,
+                &DirCopyOptions {
+                    copy_inside: true,
+                    ..DirCopyOptions::new()
+                },
+            )?;
+        }
+        next += 1;
+    }
+    // Remove `.old` if only generations were in it
+    let _ = remove_dir(from.join(".old"));
+    Ok(())
+}
+
+/// Replace the files in `directory` with the ones saved in `generation`
+///
+/// The current files are saved as a new generation first, so that the rollback can be undone.
+/// Returns the number of the new generation.
+pub fn restore(directory: &Path, generation: usize) -> Result<usize> {
+    let generation_dir = path(directory, generation);
+    ensure!(
+        generation_dir.is_dir(),
+        "Generation {generation} does not exist"
+    );
+
+    let mut current = Vec::new();
+    for entry in read_dir(directory)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            current.push(entry.path());
+        }
+    }
+    let saved = snapshot(directory, &[], &current)?;
+
+    for entry in read_dir(generation_dir)? {
+        let entry = entry?;
+        link_or_copy(&entry.path(), &directory.join(entry.file_name()))?;
+    }
+    Ok(saved)
+}
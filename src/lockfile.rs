@@ -0,0 +1,248 @@
+use crate::retry::retry_request;
+use anyhow::{Context as _, Result};
+use furse::structures::file_structs::HashAlgo;
+use libium::{
+    config::structs::{Mod, ModIdentifier},
+    iter_ext::IterExt as _,
+    upgrade::DownloadData,
+    CURSEFORGE_API, MODRINTH_API,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+/// The files resolved for each profile and modpack, keyed by their names
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Lockfile {
+    pub profiles: BTreeMap<String, Vec<LockedFile>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub modpacks: BTreeMap<String, LockedModpack>,
+}
+
+/// The files resolved for a modpack when it was last upgraded, used to upgrade it offline
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LockedModpack {
+    /// The downloaded modpack file
+    pub file: PathBuf,
+    pub files: Vec<LockedModpackFile>,
+}
+
+/// A file in a modpack, with enough information to download and check it again
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LockedModpackFile {
+    pub url: Url,
+    /// The path of the file relative to the modpack's output directory
+    pub output: PathBuf,
+    /// The length of the file in bytes
+    pub size: usize,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sha1: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sha512: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub md5: Option<String>,
+}
+
+/// A file that was resolved for a mod, with enough information to download and check it again
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LockedFile {
+    pub name: String,
+    pub project: ModIdentifier,
+    /// The Modrinth version ID, CurseForge file ID, or GitHub release tag
+    pub file_id: String,
+    pub url: Url,
+    pub filename: String,
+    /// The length of the file in bytes
+    pub size: usize,
+    /// The hashes are not available for files from GitHub Releases
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sha1: Option<String>,
+    /// Only provided by Modrinth
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sha512: Option<String>,
+    /// Only provided by CurseForge
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub md5: Option<String>,
+}
+
+/// The hashes of a file provided by its platform
+#[derive(Debug, Clone, Default)]
+struct Hashes {
+    sha1: Option<String>,
+    sha512: Option<String>,
+    md5: Option<String>,
+}
+
+/// The platform specific ID of a file, determined from its download URL
+pub enum FileId {
+    Modrinth(String),
+    CurseForge(i32),
+    GitHub(String),
+}
+
+impl FileId {
+    /// Modrinth's CDN uses `/data/{project_id}/versions/{version_id}/{filename}`,
+    /// CurseForge's uses `/files/{file_id / 1000}/{file_id % 1000}/{filename}`,
+    /// and GitHub uses `/{owner}/{repo}/releases/download/{tag}/{filename}`.
+    pub fn from_url(url: &Url) -> Option<Self> {
+        let segments = url.path_segments()?.collect_vec();
+        match url.host_str()? {
+            "cdn.modrinth.com" => Some(Self::Modrinth(segments.get(3)?.to_string())),
+            host if host.ends_with("forgecdn.net") => Some(Self::CurseForge(
+                segments.get(1)?.parse::<i32>().ok()? * 1000
+                    + segments.get(2)?.parse::<i32>().ok()?,
+            )),
+            _ => Some(Self::GitHub(
+                segments
+                    .iter()
+                    .position(|s| *s == "download")
+                    .and_then(|i| segments.get(i + 1))?
+                    .to_string(),
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for FileId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Modrinth(id) | Self::GitHub(id) => write!(f, "{id}"),
+            Self::CurseForge(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+/// Get the name of the platform that `project` is from
+pub fn platform(project: &ModIdentifier) -> &'static str {
+    match project {
+        ModIdentifier::CurseForgeProject(_) => "CurseForge",
+        ModIdentifier::GitHubRepository(..) => "GitHub",
+        _ => "Modrinth",
+    }
+}
+
+/// Get the path of the lockfile that belongs to the config file at `config_path`
+pub fn path(config_path: &Path) -> PathBuf {
+    config_path.with_extension("lock.json")
+}
+
+/// Read the lockfile at `path`, or an empty lockfile if it doesn't exist yet
+pub fn read(path: &Path) -> Result<Lockfile> {
+    if path.exists() {
+        Ok(serde_json::from_str(&read_to_string(path)?)
+            .with_context(|| format!("Could not parse the lockfile at {}", path.display()))?)
+    } else {
+        Ok(Lockfile::default())
+    }
+}
+
+/// Write `lockfile` to `path`
+pub fn write_file(path: &Path, lockfile: &Lockfile) -> Result<()> {
+    write(path, serde_json::to_string_pretty(lockfile)?)?;
+    Ok(())
+}
+
+/// Move the files locked for the profile named `old_name` to `new_name` in the lockfile at `path`, if there are any
+pub fn rename_profile(path: &Path, old_name: &str, new_name: &str) -> Result<()> {
+    let mut lockfile = read(path)?;
+    if let Some(files) = lockfile.profiles.remove(old_name) {
+        lockfile.profiles.insert(new_name.to_owned(), files);
+        write_file(path, &lockfile)?;
+    }
+    Ok(())
+}
+
+/// Determine the file ID and hash of the `resolved` files and create lock entries for them
+///
+/// Uses a maximum of 2 network requests, one for each platform that provides hashes.
+/// Modrinth provides SHA-1 and SHA-512 hashes, and CurseForge provides SHA-1 and MD5 hashes.
+pub async fn lock(resolved: Vec<(Mod, DownloadData)>) -> Result<Vec<LockedFile>> {
+    let file_ids = resolved
+        .iter()
+        .map(|(mod_, downloadable)| {
+            FileId::from_url(&downloadable.download_url).with_context(|| {
+                format!(
+                    "Could not determine the file ID of {} from {}",
+                    mod_.name, downloadable.download_url
+                )
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mr_ids = file_ids
+        .iter()
+        .filter_map(|id| match id {
+            FileId::Modrinth(id) => Some(id.as_str()),
+            _ => None,
+        })
+        .collect_vec();
+    let cf_ids = file_ids
+        .iter()
+        .filter_map(|id| match id {
+            FileId::CurseForge(id) => Some(*id),
+            _ => None,
+        })
+        .collect_vec();
+
+    let mut hashes = BTreeMap::new();
+    if !mr_ids.is_empty() {
+        for version in retry_request(|| MODRINTH_API.get_multiple_versions(&mr_ids)).await? {
+            for file in version.files {
+                hashes.insert(
+                    file.url.to_string(),
+                    Hashes {
+                        sha1: Some(file.hashes.sha1),
+                        sha512: Some(file.hashes.sha512),
+                        md5: None,
+                    },
+                );
+            }
+        }
+    }
+    if !cf_ids.is_empty() {
+        for file in retry_request(|| CURSEFORGE_API.get_files(cf_ids.clone())).await? {
+            if let Some(url) = file.download_url {
+                let find = |algo: fn(&HashAlgo) -> bool| {
+                    file.hashes
+                        .iter()
+                        .find(|hash| algo(&hash.algo))
+                        .map(|hash| hash.value.clone())
+                };
+                hashes.insert(
+                    url.to_string(),
+                    Hashes {
+                        sha1: find(|algo| matches!(algo, HashAlgo::Sha1)),
+                        sha512: None,
+                        md5: find(|algo| matches!(algo, HashAlgo::Md5)),
+                    },
+                );
+            }
+        }
+    }
+
+    Ok(resolved
+        .into_iter()
+        .zip(file_ids)
+        .map(|((mod_, downloadable), file_id)| {
+            let hashes = hashes
+                .get(downloadable.download_url.as_str())
+                .cloned()
+                .unwrap_or_default();
+            LockedFile {
+                filename: downloadable.filename(),
+                sha1: hashes.sha1,
+                sha512: hashes.sha512,
+                md5: hashes.md5,
+                name: mod_.name,
+                project: mod_.identifier,
+                file_id: file_id.to_string(),
+                url: downloadable.download_url,
+                size: downloadable.length,
+            }
+        })
+        .collect_vec())
+}
@@ -16,14 +16,28 @@
 #![expect(clippy::multiple_crate_versions, clippy::too_many_lines)]
 
 mod add;
+mod cache;
+mod changelog;
 mod cli;
+mod conflicts;
+mod dedup;
 mod download;
 mod file_picker;
+mod generations;
+mod lockfile;
+mod metadata;
+mod prompt;
+mod report;
+mod retry;
+mod settings;
 mod subcommands;
 
 use anyhow::{anyhow, bail, ensure, Result};
 use clap::{CommandFactory, Parser};
-use cli::{Ferium, ModpackSubCommands, ProfileSubCommands, SubCommands};
+use cli::{
+    CacheSubCommands, ExportFormat, Ferium, ListFormat, ModpackSubCommands, ProfileSubCommands,
+    SubCommands,
+};
 use colored::{ColoredString, Colorize};
 use indicatif::ProgressStyle;
 use libium::{
@@ -35,11 +49,16 @@ use libium::{
     },
     iter_ext::IterExt as _,
 };
+use retry::{RetryPolicy, RETRY_POLICY};
 use std::{
+    collections::HashMap,
     env::{set_var, var_os},
+    path::Path,
     process::ExitCode,
     sync::{LazyLock, OnceLock},
+    time::Duration,
 };
+use subcommands::outdated::{UpdatesAvailable, UPDATES_AVAILABLE_EXIT_CODE};
 
 const CROSS: &str = "×";
 static TICK: LazyLock<ColoredString> = LazyLock::new(|| "✓".green());
@@ -86,6 +105,10 @@ fn main() -> ExitCode {
     let runtime = builder.build().expect("Could not initialise Tokio runtime");
 
     if let Err(err) = runtime.block_on(actual_main(cli)) {
+        if let Some(updates) = err.downcast_ref::<UpdatesAvailable>() {
+            println!("\n{}", updates.to_string().yellow().bold());
+            return ExitCode::from(UPDATES_AVAILABLE_EXIT_CODE);
+        }
         if !err.to_string().is_empty() {
             eprintln!("{}", err.to_string().red().bold());
             if err
@@ -99,7 +122,7 @@ fn main() -> ExitCode {
             {
                 eprintln!(
                     "{}",
-                    "Verify that you are connnected to the internet"
+                    "Verify that you are connnected to the internet, or use `--offline` to upgrade using previously downloaded files"
                         .yellow()
                         .bold()
                 );
@@ -145,13 +168,26 @@ async fn actual_main(mut cli_app: Ferium) -> Result<()> {
     if let Some(n) = cli_app.parallel_network {
         let _ = PARALLEL_NETWORK.set(n);
     }
+    let _ = prompt::NO_INPUT.set(cli_app.no_input);
+    let default_retry = RetryPolicy::default();
+    let _ = RETRY_POLICY.set(RetryPolicy {
+        retries: cli_app.retries.unwrap_or(default_retry.retries),
+        initial_delay: cli_app
+            .retry_delay
+            .map_or(default_retry.initial_delay, Duration::from_millis),
+        max_delay: cli_app
+            .max_retry_delay
+            .map_or(default_retry.max_delay, Duration::from_secs),
+    });
 
-    let mut config_file = config::get_file(
-        &cli_app
-            .config_file
-            .or_else(|| var_os("FERIUM_CONFIG_FILE").map(Into::into))
-            .unwrap_or(DEFAULT_CONFIG_PATH.clone()),
-    )?;
+    let config_path = cli_app
+        .config_file
+        .or_else(|| var_os("FERIUM_CONFIG_FILE").map(Into::into))
+        .unwrap_or(DEFAULT_CONFIG_PATH.clone());
+    let lockfile_path = lockfile::path(&config_path);
+    let settings_path = settings::path(&config_path);
+    let mut settings = settings::read(&settings_path)?;
+    let mut config_file = config::get_file(&config_path)?;
     let mut config = config::deserialise(&libium::read_wrapper(&mut config_file)?)?;
 
     let mut did_add_fail = false;
@@ -235,12 +271,20 @@ async fn actual_main(mut cli_app: Ferium) -> Result<()> {
 
             did_add_fail = add::display_successes_failures(&successes, failures);
         }
-        SubCommands::List { verbose, markdown } => {
+        SubCommands::List {
+            verbose,
+            markdown,
+            refresh,
+            format,
+        } => {
             let profile = get_active_profile(&mut config)?;
             check_empty_profile(profile)?;
+            let profile_settings = settings.profile(&profile.name);
 
-            if verbose {
-                subcommands::list::verbose(profile, markdown).await?;
+            if format == ListFormat::Json {
+                subcommands::list::json(profile, &profile_settings, verbose, refresh).await?;
+            } else if verbose {
+                subcommands::list::verbose(profile, markdown, refresh).await?;
             } else {
                 println!(
                     "{} {} on {} {}\n",
@@ -260,28 +304,73 @@ async fn actual_main(mut cli_app: Ferium) -> Result<()> {
                         .display(", ")
                         .green(),
                 );
+                let newer = if profile
+                    .mods
+                    .iter()
+                    .any(|mod_| subcommands::pin::pinned_to(mod_, &profile_settings).is_some())
+                {
+                    subcommands::pin::newer_versions(profile, &profile_settings).await
+                } else {
+                    HashMap::new()
+                };
                 for mod_ in &profile.mods {
                     println!(
-                        "{:20}  {}",
+                        "{:20}  {}{}",
                         match &mod_.identifier {
                             ModIdentifier::CurseForgeProject(id) =>
                                 format!("{} {:8}", "CF".red(), id.to_string().dimmed()),
-                            ModIdentifier::ModrinthProject(id) =>
+                            ModIdentifier::ModrinthProject(id)
+                            | ModIdentifier::PinnedModrinthProject(id, _) =>
                                 format!("{} {:8}", "MR".green(), id.dimmed()),
                             ModIdentifier::GitHubRepository(..) => "GH".purple().to_string(),
                             _ => todo!(),
                         },
                         match &mod_.identifier {
                             ModIdentifier::ModrinthProject(_)
+                            | ModIdentifier::PinnedModrinthProject(..)
                             | ModIdentifier::CurseForgeProject(_) => mod_.name.bold().to_string(),
                             ModIdentifier::GitHubRepository(owner, repo) =>
                                 format!("{}/{}", owner.dimmed(), repo.bold()),
                             _ => todo!(),
                         },
+                        subcommands::pin::pinned_to(mod_, &profile_settings)
+                            .map(|pin| {
+                                format!(
+                                    "  {}{}",
+                                    format!("pinned to {pin}").dimmed(),
+                                    newer
+                                        .get(&mod_.name)
+                                        .map(|filename| format!(" ({filename} available)").yellow())
+                                        .unwrap_or_default()
+                                )
+                            })
+                            .unwrap_or_default(),
                     );
                 }
             }
         }
+        SubCommands::Cache { subcommand } => match subcommand {
+            CacheSubCommands::Prune { older_than } => subcommands::cache::prune(older_than)?,
+            CacheSubCommands::Stats => subcommands::cache::stats()?,
+        },
+        SubCommands::Lock => {
+            let profile = get_active_profile(&mut config)?;
+            check_empty_profile(profile)?;
+            let resolution =
+                subcommands::lock(profile, settings.profile_mut(&profile.name), &lockfile_path)
+                    .await?;
+            settings::write_file(&settings_path, &settings)?;
+            ensure!(
+                !resolution.error(),
+                "\nCould not get the latest compatible version of some mods, the lockfile was not updated"
+            );
+            println!(
+                "\n{} Locked {} files in {}",
+                &*TICK,
+                resolution.locked.len(),
+                lockfile_path.display().to_string().blue().underline()
+            );
+        }
         SubCommands::Modpack { subcommand } => {
             let mut default_flag = false;
             let subcommand = subcommand.unwrap_or_else(|| {
@@ -294,7 +383,14 @@ async fn actual_main(mut cli_app: Ferium) -> Result<()> {
                     output_dir,
                     install_overrides,
                 } => {
-                    if let Ok(project_id) = identifier.parse::<i32>() {
+                    if Path::new(&identifier).is_file() {
+                        subcommands::modpack::add::local(
+                            &mut config,
+                            Path::new(&identifier),
+                            output_dir,
+                            install_overrides,
+                        )?;
+                    } else if let Ok(project_id) = identifier.parse::<i32>() {
                         subcommands::modpack::add::curseforge(
                             &mut config,
                             project_id,
@@ -322,18 +418,29 @@ async fn actual_main(mut cli_app: Ferium) -> Result<()> {
                 ModpackSubCommands::Configure {
                     output_dir,
                     install_overrides,
+                    side,
                 } => {
-                    subcommands::modpack::configure(
-                        get_active_modpack(&mut config)?,
-                        output_dir,
-                        install_overrides,
-                    )?;
+                    let modpack = get_active_modpack(&mut config)?;
+                    // Don't configure the rest interactively if only the side was provided
+                    if side.is_none() || output_dir.is_some() || install_overrides.is_some() {
+                        subcommands::modpack::configure(modpack, output_dir, install_overrides)?;
+                    }
+                    if let Some(side) = side {
+                        settings.modpack_mut(&modpack.name).side = side;
+                        settings::write_file(&settings_path, &settings)?;
+                    }
                 }
                 ModpackSubCommands::Delete {
                     modpack_name,
                     switch_to,
                 } => {
-                    subcommands::modpack::delete(&mut config, modpack_name, switch_to)?;
+                    subcommands::modpack::delete(
+                        &mut config,
+                        &mut settings,
+                        modpack_name,
+                        switch_to,
+                    )?;
+                    settings::write_file(&settings_path, &settings)?;
                 }
                 ModpackSubCommands::Info => {
                     subcommands::modpack::info(get_active_modpack(&mut config)?, true);
@@ -346,8 +453,22 @@ async fn actual_main(mut cli_app: Ferium) -> Result<()> {
                 ModpackSubCommands::Switch { modpack_name } => {
                     subcommands::modpack::switch(&mut config, modpack_name)?;
                 }
-                ModpackSubCommands::Upgrade => {
-                    subcommands::modpack::upgrade(get_active_modpack(&mut config)?).await?;
+                ModpackSubCommands::Upgrade {
+                    dry_run,
+                    instance,
+                    report,
+                } => {
+                    let modpack = get_active_modpack(&mut config)?;
+                    subcommands::modpack::upgrade(
+                        modpack,
+                        &settings.modpack(&modpack.name),
+                        dry_run,
+                        instance,
+                        &lockfile_path,
+                        cli_app.offline,
+                        report.as_deref(),
+                    )
+                    .await?;
                 }
             };
             if default_flag {
@@ -358,6 +479,31 @@ async fn actual_main(mut cli_app: Ferium) -> Result<()> {
                 );
             }
         }
+        SubCommands::Outdated => {
+            let profile = get_active_profile(&mut config)?;
+            check_empty_profile(profile)?;
+            let result = subcommands::outdated::outdated(
+                profile,
+                settings.profile_mut(&profile.name),
+                &lockfile_path,
+            )
+            .await;
+            // Save the optional dependencies that were picked even if there are updates available
+            settings::write_file(&settings_path, &settings)?;
+            result?;
+        }
+        SubCommands::Pin { mod_name, version } => {
+            let profile = get_active_profile(&mut config)?;
+            check_empty_profile(profile)?;
+            subcommands::pin::pin(
+                profile,
+                settings.profile_mut(&profile.name),
+                &mod_name,
+                version,
+            )
+            .await?;
+            settings::write_file(&settings_path, &settings)?;
+        }
         SubCommands::Profile { subcommand } => {
             let mut default_flag = false;
             let subcommand = subcommand.unwrap_or_else(|| {
@@ -370,15 +516,51 @@ async fn actual_main(mut cli_app: Ferium) -> Result<()> {
                     mod_loaders,
                     name,
                     output_dir,
+                    side,
+                    optional_dependencies,
                 } => {
-                    subcommands::profile::configure(
-                        get_active_profile(&mut config)?,
-                        game_versions,
-                        mod_loaders,
-                        name,
-                        output_dir,
-                    )
-                    .await?;
+                    let profile = get_active_profile(&mut config)?;
+                    let old_name = profile.name.clone();
+                    let old_output_dir = profile.output_dir.clone();
+                    // Don't configure the rest interactively if only settings were provided
+                    if (side.is_none() && optional_dependencies.is_none())
+                        || !game_versions.is_empty()
+                        || !mod_loaders.is_empty()
+                        || name.is_some()
+                        || output_dir.is_some()
+                    {
+                        subcommands::profile::configure(
+                            profile,
+                            game_versions,
+                            mod_loaders,
+                            name,
+                            output_dir,
+                        )
+                        .await?;
+                    }
+                    // Keep the profile's settings and locked files if it was renamed
+                    if old_name != profile.name {
+                        if let Some(profile_settings) = settings.profiles.remove(&old_name) {
+                            settings
+                                .profiles
+                                .insert(profile.name.clone(), profile_settings);
+                            settings::write_file(&settings_path, &settings)?;
+                        }
+                        lockfile::rename_profile(&lockfile_path, &old_name, &profile.name)?;
+                    }
+                    // Keep the profile's generations if its output directory was changed
+                    if old_output_dir != profile.output_dir {
+                        generations::relocate(&old_output_dir, &profile.output_dir)?;
+                    }
+                    if let Some(side) = side {
+                        settings.profile_mut(&profile.name).side = side;
+                        settings::write_file(&settings_path, &settings)?;
+                    }
+                    if let Some(optional_dependencies) = optional_dependencies {
+                        settings.profile_mut(&profile.name).optional_dependencies =
+                            optional_dependencies;
+                        settings::write_file(&settings_path, &settings)?;
+                    }
                 }
                 ProfileSubCommands::Create {
                     import,
@@ -386,6 +568,8 @@ async fn actual_main(mut cli_app: Ferium) -> Result<()> {
                     mod_loader,
                     name,
                     output_dir,
+                    side,
+                    optional_dependencies,
                 } => {
                     subcommands::profile::create(
                         &mut config,
@@ -400,12 +584,69 @@ async fn actual_main(mut cli_app: Ferium) -> Result<()> {
                         output_dir,
                     )
                     .await?;
+                    if side.is_some() || optional_dependencies.is_some() {
+                        // The new profile is the active one
+                        let profile = get_active_profile(&mut config)?;
+                        let profile_settings = settings.profile_mut(&profile.name);
+                        if let Some(side) = side {
+                            profile_settings.side = side;
+                        }
+                        if let Some(optional_dependencies) = optional_dependencies {
+                            profile_settings.optional_dependencies = optional_dependencies;
+                        }
+                        settings::write_file(&settings_path, &settings)?;
+                    }
                 }
                 ProfileSubCommands::Delete {
                     profile_name,
                     switch_to,
                 } => {
-                    subcommands::profile::delete(&mut config, profile_name, switch_to)?;
+                    subcommands::profile::delete(
+                        &mut config,
+                        &mut settings,
+                        profile_name,
+                        switch_to,
+                    )?;
+                    settings::write_file(&settings_path, &settings)?;
+                }
+                ProfileSubCommands::Export {
+                    format,
+                    output,
+                    pack_version,
+                    loader_version,
+                    overrides,
+                } => {
+                    let profile = get_active_profile(&mut config)?;
+                    check_empty_profile(profile)?;
+                    let profile_settings = settings.profile_mut(&profile.name);
+                    let output = output.unwrap_or_else(|| {
+                        format!("{}.{}", profile.name, format.extension()).into()
+                    });
+                    match format {
+                        ExportFormat::Mrpack => {
+                            subcommands::profile::export::mrpack(
+                                profile,
+                                profile_settings,
+                                &output,
+                                pack_version,
+                                loader_version,
+                                overrides.as_deref(),
+                            )
+                            .await?;
+                        }
+                        ExportFormat::Curseforge => {
+                            subcommands::profile::export::curseforge(
+                                profile,
+                                profile_settings,
+                                &output,
+                                pack_version,
+                                loader_version,
+                                overrides.as_deref(),
+                            )
+                            .await?;
+                        }
+                    }
+                    settings::write_file(&settings_path, &settings)?;
                 }
                 ProfileSubCommands::Info => {
                     subcommands::profile::info(get_active_profile(&mut config)?, true);
@@ -432,12 +673,72 @@ async fn actual_main(mut cli_app: Ferium) -> Result<()> {
         SubCommands::Remove { mod_names } => {
             let profile = get_active_profile(&mut config)?;
             check_empty_profile(profile)?;
-            subcommands::remove(profile, mod_names)?;
+            let profile_settings = settings.profile_mut(&profile.name);
+            subcommands::remove(profile, profile_settings, mod_names)?;
+            settings::write_file(&settings_path, &settings)?;
+        }
+        SubCommands::Rollback { to, list, prune } => {
+            let profile = get_active_profile(&mut config)?;
+            if list {
+                subcommands::rollback::list(&profile.output_dir)?;
+            } else if let Some(retention) = prune {
+                subcommands::rollback::prune(&profile.output_dir, retention)?;
+            } else {
+                subcommands::rollback::rollback(&profile.output_dir, to)?;
+            }
+        }
+        SubCommands::Search {
+            query,
+            platform,
+            limit,
+        } => {
+            let profile = get_active_profile(&mut config)?;
+            let ids = subcommands::search(profile, &query.join(" "), platform, limit).await?;
+            if !ids.is_empty() {
+                let (successes, failures) = libium::add(profile, ids, true, false, vec![]).await?;
+                did_add_fail = add::display_successes_failures(&successes, failures);
+            }
+        }
+        SubCommands::Tree => {
+            let profile = get_active_profile(&mut config)?;
+            check_empty_profile(profile)?;
+            let result = subcommands::tree(profile, settings.profile_mut(&profile.name)).await;
+            settings::write_file(&settings_path, &settings)?;
+            result?;
         }
-        SubCommands::Upgrade => {
+        SubCommands::Unpin { mod_name } => {
+            let profile = get_active_profile(&mut config)?;
+            check_empty_profile(profile)?;
+            subcommands::pin::unpin(profile, settings.profile_mut(&profile.name), &mod_name)?;
+            settings::write_file(&settings_path, &settings)?;
+        }
+        SubCommands::Upgrade {
+            locked,
+            dry_run,
+            changelog,
+            report,
+        } => {
+            ensure!(
+                !cli_app.offline || changelog.is_none(),
+                "Changelogs cannot be shown offline"
+            );
             let profile = get_active_profile(&mut config)?;
             check_empty_profile(profile)?;
-            subcommands::upgrade(profile).await?;
+            let result = subcommands::upgrade(
+                profile,
+                settings.profile_mut(&profile.name),
+                &lockfile_path,
+                locked,
+                cli_app.offline,
+                dry_run,
+                changelog.is_some(),
+                changelog.flatten().as_deref(),
+                report.as_deref(),
+            )
+            .await;
+            // Save the optional dependencies that were picked even if some mods failed
+            settings::write_file(&settings_path, &settings)?;
+            result?;
         }
     };
 
@@ -464,6 +765,10 @@ fn get_active_profile(config: &mut Config) -> Result<&mut Profile> {
         }
         1 => config.active_profile = 0,
         n if config.active_profile >= n => {
+            ensure!(
+                prompt::can_prompt(),
+                "Active profile specified incorrectly, pick a profile to use with `ferium profile switch <name>`"
+            );
             println!(
                 "{}",
                 "Active profile specified incorrectly, please pick a profile to use"
@@ -483,6 +788,10 @@ fn get_active_modpack(config: &mut Config) -> Result<&mut Modpack> {
         0 => bail!("There are no modpacks configured, add a modpack using `ferium modpack add`"),
         1 => config.active_modpack = 0,
         n if n <= config.active_modpack => {
+            ensure!(
+                prompt::can_prompt(),
+                "Active modpack specified incorrectly, pick a modpack to use with `ferium modpack switch <name>`"
+            );
             println!(
                 "{}",
                 "Active modpack specified incorrectly, please pick a modpack to use"
@@ -0,0 +1,161 @@
+use anyhow::{Context as _, Result};
+use libium::{GITHUB_API, HOME};
+use octocrab::map_github_error;
+use reqwest::{
+    header::{HeaderMap, ETAG, IF_NONE_MATCH},
+    StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, read_to_string, write},
+    path::PathBuf,
+    sync::LazyLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The file project metadata is cached in
+pub static METADATA_CACHE_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
+    HOME.join(".config")
+        .join("ferium")
+        .join(".cache")
+        .join("metadata.json")
+});
+
+/// How long cached metadata is used for before it is fetched or revalidated again
+pub const METADATA_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A cached API response
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Entry {
+    /// When the response was fetched or last revalidated, as a UNIX timestamp
+    fetched_at: u64,
+    /// The `ETag` header of the response, used to revalidate it without downloading it again
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    etag: Option<String>,
+    value: Value,
+}
+
+/// API responses cached across runs, keyed by the platform and the resource, e.g. `modrinth/project/{id}`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MetadataCache {
+    entries: BTreeMap<String, Entry>,
+    /// Entries fetched before this UNIX timestamp are not fresh, regardless of the TTL
+    #[serde(skip)]
+    fresh_after: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+impl MetadataCache {
+    /// Read the metadata cache, or an empty one if it doesn't exist or can't be parsed
+    ///
+    /// If `refresh` is true, only the entries fetched from now on are considered fresh.
+    pub fn read(refresh: bool) -> Self {
+        let mut cache: Self = read_to_string(&*METADATA_CACHE_PATH)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        if refresh {
+            cache.fresh_after = now();
+        }
+        cache
+    }
+
+    /// Write the metadata cache to [`METADATA_CACHE_PATH`]
+    pub fn write(&self) -> Result<()> {
+        if let Some(parent) = METADATA_CACHE_PATH.parent() {
+            create_dir_all(parent)?;
+        }
+        write(&*METADATA_CACHE_PATH, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Get the value of `key` if it was fetched within the [`METADATA_TTL`]
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.entries
+            .get(key)
+            .filter(|entry| {
+                entry.fetched_at >= self.fresh_after
+                    && now().saturating_sub(entry.fetched_at) < METADATA_TTL.as_secs()
+            })
+            .and_then(|entry| serde_json::from_value(entry.value.clone()).ok())
+    }
+
+    /// Get the `ETag` of `key`, regardless of whether it is fresh
+    pub fn etag(&self, key: &str) -> Option<String> {
+        self.entries.get(key).and_then(|entry| entry.etag.clone())
+    }
+
+    /// Cache `value` as `key`, along with the `etag` of the response it came from
+    pub fn insert<T: Serialize>(
+        &mut self,
+        key: String,
+        value: &T,
+        etag: Option<String>,
+    ) -> Result<()> {
+        self.entries.insert(
+            key,
+            Entry {
+                fetched_at: now(),
+                etag,
+                value: serde_json::to_value(value)?,
+            },
+        );
+        Ok(())
+    }
+
+    /// Mark `key` as fresh because the server responded that it has not changed
+    pub fn revalidate(&mut self, key: &str) -> Result<()> {
+        self.entries
+            .get_mut(key)
+            .context("Revalidated metadata that is not cached")?
+            .fetched_at = now();
+        Ok(())
+    }
+}
+
+/// The response to a conditional request
+pub enum Conditional<T> {
+    /// The resource has not changed since the `ETag` that was sent
+    NotModified,
+    Modified {
+        value: T,
+        etag: Option<String>,
+    },
+}
+
+/// Get `path` from the GitHub API using [`GITHUB_API`], making a conditional request with `etag` if provided
+///
+/// Conditional requests that are answered with `304 Not Modified` don't count towards GitHub's rate limit.
+/// Only GitHub responses are revalidated this way, Modrinth and CurseForge responses are fetched again once they expire.
+pub async fn github<T: DeserializeOwned>(
+    path: &str,
+    etag: Option<String>,
+) -> Result<Conditional<T>> {
+    let mut headers = HeaderMap::new();
+    if let Some(etag) = etag {
+        headers.insert(IF_NONE_MATCH, etag.parse()?);
+    }
+    let response = GITHUB_API
+        ._get_with_headers(format!("/{path}"), Some(headers))
+        .await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Conditional::NotModified);
+    }
+    let response = map_github_error(response).await?;
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(ToOwned::to_owned);
+    Ok(Conditional::Modified {
+        value: serde_json::from_str(&GITHUB_API.body_to_string(response).await?)?,
+        etag,
+    })
+}
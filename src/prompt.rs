@@ -0,0 +1,21 @@
+use anyhow::{anyhow, Error};
+use std::{
+    io::{stdin, IsTerminal as _},
+    sync::OnceLock,
+};
+
+/// Whether prompts were disabled using `--no-input`
+pub static NO_INPUT: OnceLock<bool> = OnceLock::new();
+
+/// Whether the user can be prompted for input
+///
+/// Prompts are disabled using `--no-input`, or when stdin is not a terminal, e.g. in scripts and containers.
+pub fn can_prompt() -> bool {
+    !NO_INPUT.get().copied().unwrap_or_default() && stdin().is_terminal()
+}
+
+/// Get the error for when `what` could not be asked for because prompts are disabled,
+/// telling the user to provide `instead` as an argument
+pub fn required(what: &str, instead: &str) -> Error {
+    anyhow!("Cannot ask for {what} because input is disabled, provide {instead} instead")
+}
@@ -0,0 +1,152 @@
+use crate::{
+    cache,
+    download::{CleanPlan, Downloadable},
+    lockfile::{platform, LockedFile},
+};
+use anyhow::Result;
+use reqwest::Url;
+use serde::Serialize;
+use std::{
+    ffi::OsString,
+    fs::write,
+    path::{Path, PathBuf},
+};
+
+/// What an upgrade of a profile or modpack did, written as JSON using `--report`
+#[derive(Serialize, Debug, Default)]
+pub struct Report {
+    /// Whether nothing was changed, in which case the rest is what would have been done
+    pub dry_run: bool,
+    /// The files resolved for the mods, or for a modpack, the files in it
+    pub resolved: Vec<ResolvedFile>,
+    /// The mods that could not be resolved, and the files that could not be downloaded
+    pub failures: Vec<Failure>,
+    /// The mods that were resolved as dependencies of other mods
+    pub dependencies_added: Vec<AddedDependency>,
+    /// The files that were moved to a new generation in `.old`
+    pub moved_to_old: Vec<String>,
+    /// The `.part` files of previous downloads that were deleted
+    pub deleted: Vec<String>,
+    /// The files that were downloaded or installed from the download cache
+    pub downloaded: Vec<DownloadedFile>,
+    /// The `user` folder mods of a profile or the overrides of a modpack that were installed
+    pub installed: Vec<String>,
+}
+
+/// A file that was resolved for a mod or a file in a modpack
+#[derive(Serialize, Debug)]
+pub struct ResolvedFile {
+    /// Not known for the files in a modpack
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<&'static str>,
+    /// The Modrinth version ID, CurseForge file ID, or GitHub release tag
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<String>,
+    pub filename: String,
+    pub url: Url,
+    /// The length of the file in bytes
+    pub size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+}
+
+impl From<&LockedFile> for ResolvedFile {
+    fn from(value: &LockedFile) -> Self {
+        Self {
+            name: Some(value.name.clone()),
+            platform: Some(platform(&value.project)),
+            file_id: Some(value.file_id.clone()),
+            filename: value.filename.clone(),
+            url: value.url.clone(),
+            size: value.size,
+            sha1: value.sha1.clone(),
+        }
+    }
+}
+
+impl From<&Downloadable> for ResolvedFile {
+    fn from(value: &Downloadable) -> Self {
+        Self {
+            name: None,
+            platform: None,
+            file_id: None,
+            filename: value.output.to_string_lossy().to_string(),
+            url: value.url.clone(),
+            size: value.length,
+            sha1: value.sha1.clone(),
+        }
+    }
+}
+
+/// A mod or a file in a modpack that could not be resolved, or a file that could not be downloaded
+#[derive(Serialize, Debug, Clone)]
+pub struct Failure {
+    pub name: String,
+    pub error: String,
+}
+
+/// A mod that was resolved because other mods depend on it
+#[derive(Serialize, Debug)]
+pub struct AddedDependency {
+    pub name: String,
+    pub platform: &'static str,
+    /// The Modrinth project ID, CurseForge project ID, or GitHub repository's full name
+    pub identifier: String,
+    /// The names of the mods that depend on it
+    pub required_by: Vec<String>,
+}
+
+/// A file that was downloaded, or installed from the download cache
+#[derive(Serialize, Debug)]
+pub struct DownloadedFile {
+    pub filename: String,
+    /// The length of the file in bytes
+    pub size: usize,
+    pub from_cache: bool,
+}
+
+impl Report {
+    /// Record the changes that cleaning an output directory made according to `plan`
+    pub fn record_clean(&mut self, plan: &CleanPlan) {
+        let filename = |path: &PathBuf| {
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        };
+        self.moved_to_old.extend(plan.to_move.iter().map(filename));
+        self.deleted.extend(plan.to_delete.iter().map(filename));
+    }
+
+    /// Record the files in `to_download` as downloaded, for a dry run
+    pub fn record_planned_downloads(&mut self, to_download: &[Downloadable]) {
+        self.downloaded
+            .extend(to_download.iter().map(|downloadable| {
+                DownloadedFile {
+                    filename: downloadable.filename(),
+                    size: downloadable.length,
+                    from_cache: downloadable
+                        .sha1
+                        .as_ref()
+                        .is_some_and(|sha1| cache::path(sha1).is_file()),
+                }
+            }));
+    }
+
+    /// Record the files in `to_install` as installed
+    pub fn record_installs(&mut self, to_install: &[(OsString, PathBuf)]) {
+        self.installed.extend(
+            to_install
+                .iter()
+                .map(|(name, _)| name.to_string_lossy().to_string()),
+        );
+    }
+
+    /// Write this report as JSON to `path`
+    pub fn write_file(&self, path: &Path) -> Result<()> {
+        write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
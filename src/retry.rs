@@ -0,0 +1,186 @@
+use anyhow::{Error, Result};
+use colored::Colorize as _;
+use libium::upgrade::mod_downloadable;
+use reqwest::{header::HeaderMap, StatusCode};
+use std::{
+    future::Future,
+    sync::OnceLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How network requests that failed because of a rate limit or a temporary error are retried
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// The number of times a request is retried before giving up
+    pub retries: u32,
+    /// The delay before the first retry, which is doubled after every retry
+    pub initial_delay: Duration,
+    /// The longest delay before a retry, including delays requested by the server
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+pub static RETRY_POLICY: OnceLock<RetryPolicy> = OnceLock::new();
+
+/// The server responded with a status that is worth retrying the request for,
+/// i.e. `429 Too Many Requests` or a server error
+#[derive(Debug)]
+pub struct RetryableStatus {
+    pub status: StatusCode,
+    /// How long the server asked to wait before retrying
+    pub wait: Option<Duration>,
+}
+
+impl std::fmt::Display for RetryableStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The server responded with {}", self.status)
+    }
+}
+
+impl std::error::Error for RetryableStatus {}
+
+impl RetryableStatus {
+    /// Get the error for a response with `status` and `headers`, if the request should be retried
+    pub fn from_response(status: StatusCode, headers: &HeaderMap) -> Option<Self> {
+        (status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()).then(|| Self {
+            status,
+            wait: requested_wait(headers),
+        })
+    }
+}
+
+/// Get how long the server asked to wait before retrying, using the `Retry-After` or `X-Ratelimit-*` headers
+///
+/// Modrinth sends the number of seconds until the rate limit resets in `X-Ratelimit-Reset`,
+/// while GitHub sends the UNIX timestamp it resets at.
+pub fn requested_wait(headers: &HeaderMap) -> Option<Duration> {
+    let number = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<u64>().ok();
+    if let Some(seconds) = number("retry-after") {
+        return Some(Duration::from_secs(seconds));
+    }
+    if number("x-ratelimit-remaining")? != 0 {
+        return None;
+    }
+    let reset = number("x-ratelimit-reset")?;
+    // A timestamp is far larger than any number of seconds worth waiting for
+    Some(Duration::from_secs(if reset > 1_000_000_000 {
+        reset.saturating_sub(SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs())
+    } else {
+        reset
+    }))
+}
+
+/// Get how long to wait before retrying after the `attempt`th retry failed with `err`,
+/// or `None` if it is not worth retrying
+fn retry_delay(err: &Error, attempt: u32, policy: &RetryPolicy) -> Option<Duration> {
+    let backoff = policy
+        .initial_delay
+        .saturating_mul(2_u32.saturating_pow(attempt))
+        .min(policy.max_delay);
+    let rate_limited = |seconds| {
+        Duration::from_secs(u64::try_from(seconds).unwrap_or(u64::MAX)).min(policy.max_delay)
+    };
+
+    for cause in err.chain() {
+        if let Some(status) = cause.downcast_ref::<RetryableStatus>() {
+            return Some(
+                status
+                    .wait
+                    .map_or(backoff, |wait| wait.min(policy.max_delay)),
+            );
+        }
+        if let Some(ferinth::Error::RateLimitExceeded(seconds)) = cause.downcast_ref() {
+            return Some(rate_limited(*seconds));
+        }
+        if let Some(mod_downloadable::Error::ModrinthError(ferinth::Error::RateLimitExceeded(
+            seconds,
+        ))) = cause.downcast_ref()
+        {
+            return Some(rate_limited(*seconds));
+        }
+        // The platforms' API clients wrap the errors of the requests they make
+        let request_err = cause
+            .downcast_ref::<reqwest::Error>()
+            .or_else(|| match cause.downcast_ref() {
+                Some(ferinth::Error::ReqwestError(err)) => Some(err),
+                _ => None,
+            })
+            .or_else(|| match cause.downcast_ref() {
+                Some(furse::Error::ReqwestError(err)) => Some(err),
+                _ => None,
+            });
+        if let Some(err) = request_err {
+            if err.is_timeout()
+                || err.is_connect()
+                || err.status().is_some_and(|status| {
+                    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+                })
+            {
+                return Some(backoff);
+            }
+        }
+    }
+    None
+}
+
+/// Run `operation` until it succeeds, retrying it with exponential backoff
+/// if it fails because of a rate limit or a temporary error
+///
+/// Delays requested by the server are used instead of the backoff if there are any.
+/// `on_retry` is called with the error and the delay before every retry.
+pub async fn retry<T, Fut>(
+    mut operation: impl FnMut() -> Fut,
+    on_retry: impl Fn(&Error, Duration),
+) -> Result<T>
+where
+    Fut: Future<Output = Result<T>>,
+{
+    let policy = RETRY_POLICY.get().copied().unwrap_or_default();
+    let mut attempt = 0;
+    loop {
+        match operation().await {
+            Err(err) if attempt < policy.retries => {
+                let Some(delay) = retry_delay(&err, attempt, &policy) else {
+                    return Err(err);
+                };
+                on_retry(&err, delay);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Make the API request `request` using [`retry`], printing a warning before every retry
+///
+/// Used for the requests made while resolving, outside of a progress bar.
+pub async fn retry_request<T, E, Fut>(mut request: impl FnMut() -> Fut) -> Result<T>
+where
+    Fut: Future<Output = std::result::Result<T, E>>,
+    E: Into<Error>,
+{
+    retry(
+        || {
+            let response = request();
+            async move { response.await.map_err(Into::into) }
+        },
+        |err, delay| {
+            eprintln!(
+                "{} {err}, retrying in {:.1}s",
+                "Warning:".yellow().bold(),
+                delay.as_secs_f64()
+            );
+        },
+    )
+    .await
+}
@@ -0,0 +1,167 @@
+use anyhow::{Context as _, Result};
+use clap::ValueEnum;
+use ferinth::structures::project::ProjectSupportRange;
+use furse::structures::file_structs::SortableGameVersion;
+use libium::config::structs::ModIdentifier;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+/// Settings for profiles and modpacks that are not part of the config file, keyed by their names
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Settings {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub profiles: BTreeMap<String, ProfileSettings>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub modpacks: BTreeMap<String, ModpackSettings>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProfileSettings {
+    #[serde(default)]
+    pub side: Side,
+    /// The CurseForge file IDs and GitHub release tags that mods are pinned to, keyed by [`pin_key`]
+    ///
+    /// Modrinth projects are pinned using [`ModIdentifier::PinnedModrinthProject`] instead.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub pins: BTreeMap<String, String>,
+    #[serde(default)]
+    pub optional_dependencies: OptionalDependencies,
+    /// Whether the optional dependencies that were asked about were picked,
+    /// keyed by the [`mod_key`] of the mod declaring them and then of the dependency
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub optional_choices: BTreeMap<String, BTreeMap<String, bool>>,
+}
+
+impl ProfileSettings {
+    /// Get the CurseForge file ID or GitHub release tag that the mod with `identifier` is pinned to
+    pub fn pin(&self, identifier: &ModIdentifier) -> Option<&String> {
+        pin_key(identifier).and_then(|key| self.pins.get(&key))
+    }
+}
+
+/// Get the key of a CurseForge project or GitHub repository in [`ProfileSettings::pins`]
+pub fn pin_key(identifier: &ModIdentifier) -> Option<String> {
+    match identifier {
+        ModIdentifier::CurseForgeProject(id) => Some(id.to_string()),
+        ModIdentifier::GitHubRepository(owner, repo) => Some(format!("{owner}/{repo}")),
+        _ => None,
+    }
+}
+
+/// Get a key that identifies the mod with `identifier` regardless of the version it is pinned to
+pub fn mod_key(identifier: &ModIdentifier) -> String {
+    match identifier {
+        ModIdentifier::ModrinthProject(id) | ModIdentifier::PinnedModrinthProject(id, _) => {
+            id.clone()
+        }
+        identifier => pin_key(identifier).unwrap_or_default(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ModpackSettings {
+    #[serde(default)]
+    pub side: Side,
+}
+
+/// The CurseForge game version type of the environments (client and server) that a file supports
+const ENVIRONMENT_GAME_VERSION_TYPE: i32 = 75208;
+
+/// Whether mods are installed for a Minecraft client or a dedicated server
+#[derive(Serialize, Deserialize, ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    #[default]
+    Client,
+    Server,
+}
+
+/// Which optional dependencies of a profile's mods to install
+#[derive(Serialize, Deserialize, ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OptionalDependencies {
+    /// Only install required dependencies
+    #[default]
+    RequiredOnly,
+    /// Ask which optional dependencies to install the first time a mod declares them
+    Ask,
+    /// Install all optional dependencies
+    All,
+}
+
+impl Side {
+    /// Whether a Modrinth project or modpack file with `server_side` support should be installed on this side
+    pub fn allows_modrinth(self, server_side: &ProjectSupportRange) -> bool {
+        self == Self::Client || !matches!(server_side, ProjectSupportRange::Unsupported)
+    }
+
+    /// Whether a CurseForge file compatible with `game_versions` should be installed on this side
+    ///
+    /// CurseForge lists the environments a file supports among its game versions, with their own game version type,
+    /// if its author specified them. Files without any are installed on both sides.
+    pub fn allows_curseforge(self, game_versions: &[SortableGameVersion]) -> bool {
+        let mut environments = game_versions
+            .iter()
+            .filter(|version| version.game_version_type_id == Some(ENVIRONMENT_GAME_VERSION_TYPE))
+            .peekable();
+        self == Self::Client
+            || environments.peek().is_none()
+            || environments.any(|version| version.game_version_name.eq_ignore_ascii_case("server"))
+    }
+}
+
+impl std::fmt::Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Client => write!(f, "client"),
+            Self::Server => write!(f, "server"),
+        }
+    }
+}
+
+impl Settings {
+    /// Get the settings of the profile named `name`, or the defaults if none were saved
+    pub fn profile(&self, name: &str) -> ProfileSettings {
+        self.profiles.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Get the settings of the modpack named `name`, or the defaults if none were saved
+    pub fn modpack(&self, name: &str) -> ModpackSettings {
+        self.modpacks.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Get a mutable reference to the settings of the profile named `name`, inserting the defaults if none were saved
+    pub fn profile_mut(&mut self, name: &str) -> &mut ProfileSettings {
+        self.profiles.entry(name.to_owned()).or_default()
+    }
+
+    /// Get a mutable reference to the settings of the modpack named `name`, inserting the defaults if none were saved
+    pub fn modpack_mut(&mut self, name: &str) -> &mut ModpackSettings {
+        self.modpacks.entry(name.to_owned()).or_default()
+    }
+}
+
+/// Get the path of the settings file that belongs to the config file at `config_path`
+pub fn path(config_path: &Path) -> PathBuf {
+    config_path.with_extension("settings.json")
+}
+
+/// Read the settings file at `path`, or the default settings if it doesn't exist yet
+pub fn read(path: &Path) -> Result<Settings> {
+    if path.exists() {
+        Ok(serde_json::from_str(&read_to_string(path)?)
+            .with_context(|| format!("Could not parse the settings file at {}", path.display()))?)
+    } else {
+        Ok(Settings::default())
+    }
+}
+
+/// Write `settings` to `path`
+pub fn write_file(path: &Path, settings: &Settings) -> Result<()> {
+    write(path, serde_json::to_string_pretty(settings)?)?;
+    Ok(())
+}
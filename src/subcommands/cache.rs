@@ -0,0 +1,60 @@
+use crate::{cache, TICK};
+use anyhow::Result;
+use colored::Colorize as _;
+use libium::iter_ext::IterExt as _;
+
+/// Format `bytes` as a human readable size
+fn format_size(bytes: u64) -> String {
+    size::Size::from_bytes(bytes)
+        .format()
+        .with_base(size::Base::Base10)
+        .to_string()
+}
+
+/// Print the location of the cache, the number of files in it, and their total size
+pub fn stats() -> Result<()> {
+    let files = cache::list()?;
+    let unused = files
+        .iter()
+        .filter(|file| file.unused_for >= cache::days(cache::DEFAULT_MAX_AGE_DAYS))
+        .collect_vec();
+    println!(
+        "{}  {}",
+        "Location:".bold(),
+        cache::CACHE_DIR.display().to_string().blue().underline()
+    );
+    println!(
+        "{}     {}",
+        "Files:".bold(),
+        files.len().to_string().green()
+    );
+    println!(
+        "{}      {}",
+        "Size:".bold(),
+        format_size(files.iter().map(|file| file.size).sum()).green()
+    );
+    println!(
+        "{}    {}",
+        "Unused:".bold(),
+        format!(
+            "{} files ({}) not used in the last {} days",
+            unused.len(),
+            format_size(unused.iter().map(|file| file.size).sum()),
+            cache::DEFAULT_MAX_AGE_DAYS
+        )
+        .yellow()
+    );
+    Ok(())
+}
+
+/// Delete the cached files that have not been used in the last `days` days
+pub fn prune(days: u64) -> Result<()> {
+    let pruned = cache::prune(cache::days(days))?;
+    println!(
+        "{} Deleted {} cached file(s), freeing {}",
+        &*TICK,
+        pruned.len().to_string().bold(),
+        format_size(pruned.iter().map(|file| file.size).sum()).bold()
+    );
+    Ok(())
+}
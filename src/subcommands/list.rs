@@ -1,14 +1,26 @@
-use crate::TICK;
+use super::pin::pinned_to;
+use crate::{
+    lockfile::platform,
+    metadata::{self, Conditional, MetadataCache},
+    retry::retry_request,
+    settings::{mod_key, ProfileSettings},
+    TICK,
+};
 use anyhow::{Context as _, Result};
 use colored::Colorize as _;
 use ferinth::structures::{project::Project, user::TeamMember};
 use furse::structures::mod_structs::Mod;
 use libium::{
-    config::structs::{ModIdentifier, Profile},
+    config::{
+        filters::ProfileParameters as _,
+        structs::{ModIdentifier, Profile},
+    },
     iter_ext::IterExt as _,
-    CURSEFORGE_API, GITHUB_API, MODRINTH_API,
+    CURSEFORGE_API, MODRINTH_API,
 };
 use octocrab::models::{repos::Release, Repository};
+use serde::Serialize;
+use serde_json::Value;
 use tokio::task::JoinSet;
 
 enum Metadata {
@@ -35,65 +47,247 @@ impl Metadata {
             }
         }
     }
-}
 
-pub async fn verbose(profile: &mut Profile, markdown: bool) -> Result<()> {
-    if !markdown {
-        eprint!("Querying metadata... ");
+    /// Whether this is the metadata of the mod with `identifier`
+    fn describes(&self, identifier: &ModIdentifier) -> bool {
+        match (identifier, self) {
+            (ModIdentifier::PinnedModrinthProject(id, _), Metadata::MD(p, _)) => id == &p.id,
+            (identifier, _) => identifier == &self.id(),
+        }
+    }
+
+    /// Get the information shown by [`curseforge`], [`modrinth`], and [`github`] without formatting it
+    fn details(&self) -> Details {
+        match self {
+            Metadata::CF(p) => Details {
+                description: p.summary.trim().to_owned(),
+                link: p.links.website_url.to_string(),
+                source_url: p.links.source_url.as_ref().map(ToString::to_string),
+                downloads: p.download_count,
+                authors: p.authors.iter().map(|author| author.name.clone()).collect(),
+                categories: p
+                    .categories
+                    .iter()
+                    .map(|category| category.name.clone())
+                    .collect(),
+                license: None,
+            },
+            Metadata::MD(p, t) => Details {
+                description: p.description.clone(),
+                link: format!("https://modrinth.com/mod/{}", p.slug),
+                source_url: p.source_url.as_ref().map(ToString::to_string),
+                downloads: p.downloads,
+                authors: t
+                    .iter()
+                    .map(|member| member.user.username.clone())
+                    .collect(),
+                categories: p.categories.clone(),
+                license: Some(License {
+                    name: if p.license.name.is_empty() {
+                        "Custom".to_owned()
+                    } else {
+                        p.license.name.clone()
+                    },
+                    url: p.license.url.as_ref().map(ToString::to_string),
+                }),
+            },
+            Metadata::GH(p, r) => Details {
+                description: p.description.clone().unwrap_or_default(),
+                link: p
+                    .html_url
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default(),
+                source_url: p.html_url.as_ref().map(ToString::to_string),
+                downloads: r
+                    .iter()
+                    .flat_map(|release| &release.assets)
+                    .map(|asset| usize::try_from(asset.download_count).unwrap_or_default())
+                    .sum(),
+                authors: p.owner.iter().map(|owner| owner.login.clone()).collect(),
+                categories: p.topics.clone().unwrap_or_default(),
+                license: p.license.as_ref().map(|license| License {
+                    name: license.name.clone(),
+                    url: license.html_url.as_ref().map(ToString::to_string),
+                }),
+            },
+        }
     }
+}
+
+/// A profile in the JSON output of `ferium list`
+#[derive(Serialize)]
+struct ListedProfile<'a> {
+    name: &'a str,
+    mod_loader: Option<String>,
+    game_versions: Vec<String>,
+    mods: Vec<ListedMod>,
+}
+
+/// A mod in the JSON output of `ferium list`
+#[derive(Serialize)]
+struct ListedMod {
+    name: String,
+    platform: &'static str,
+    /// The Modrinth project ID, CurseForge project ID, or GitHub repository's full name
+    identifier: String,
+    /// The Modrinth version ID, CurseForge file ID, or GitHub release tag that the mod is pinned to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pinned_to: Option<String>,
+    /// Only included in verbose mode
+    #[serde(flatten)]
+    details: Option<Details>,
+}
+
+/// The metadata of a mod in the JSON output of `ferium list --verbose`
+#[derive(Serialize)]
+struct Details {
+    description: String,
+    link: String,
+    source_url: Option<String>,
+    downloads: usize,
+    authors: Vec<String>,
+    /// The categories on Modrinth and CurseForge, and the topics on GitHub
+    categories: Vec<String>,
+    license: Option<License>,
+}
 
+#[derive(Serialize)]
+struct License {
+    name: String,
+    url: Option<String>,
+}
+
+/// Fetch the metadata of the mods in `profile`, using the metadata cache for the ones fetched recently
+///
+/// GitHub responses are revalidated using their `ETag` once they expire, which doesn't count towards the rate limit.
+/// Modrinth and CurseForge don't support conditional requests, so their responses are fetched again instead.
+/// If `refresh` is true, everything is fetched or revalidated regardless of when it was cached.
+async fn fetch_metadata(profile: &Profile, refresh: bool) -> Result<Vec<Metadata>> {
+    let mut cache = MetadataCache::read(refresh);
+    let mut metadata = Vec::new();
     let mut tasks = JoinSet::new();
     let mut mr_ids = Vec::new();
     let mut cf_ids = Vec::new();
     for mod_ in &profile.mods {
         match mod_.identifier.clone() {
-            ModIdentifier::CurseForgeProject(project_id) => cf_ids.push(project_id),
-            ModIdentifier::ModrinthProject(project_id) => mr_ids.push(project_id),
+            ModIdentifier::CurseForgeProject(project_id) => {
+                if let Some(project) = cache.get(&format!("curseforge/mod/{project_id}")) {
+                    metadata.push(Metadata::CF(project));
+                } else {
+                    cf_ids.push(project_id);
+                }
+            }
+            ModIdentifier::ModrinthProject(project_id)
+            | ModIdentifier::PinnedModrinthProject(project_id, _) => mr_ids.push(project_id),
             ModIdentifier::GitHubRepository(owner, repo) => {
-                let repo = GITHUB_API.repos(owner, repo);
-                tasks.spawn(async move {
-                    Ok::<_, anyhow::Error>((
-                        repo.get().await?,
-                        repo.releases().list().send().await?,
-                    ))
-                });
+                for (key, path) in [
+                    (
+                        format!("github/repo/{owner}/{repo}"),
+                        format!("repos/{owner}/{repo}"),
+                    ),
+                    (
+                        format!("github/releases/{owner}/{repo}"),
+                        format!("repos/{owner}/{repo}/releases"),
+                    ),
+                ] {
+                    if cache.get::<Value>(&key).is_none() {
+                        let etag = cache.etag(&key);
+                        tasks.spawn(async move {
+                            let response =
+                                retry_request(|| metadata::github::<Value>(&path, etag.clone()))
+                                    .await?;
+                            Ok::<_, anyhow::Error>((key, response))
+                        });
+                    }
+                }
             }
             _ => todo!(),
         }
     }
 
-    let mr_projects = if mr_ids.is_empty() {
-        vec![]
-    } else {
-        MODRINTH_API
-            .get_multiple_projects(&mr_ids.iter().map(AsRef::as_ref).collect_vec())
-            .await?
-    };
-    let mr_teams_members = if mr_projects.is_empty() {
-        vec![]
-    } else {
-        MODRINTH_API
-            .list_multiple_teams_members(&mr_projects.iter().map(|p| p.team.as_ref()).collect_vec())
-            .await?
-    };
-
-    let cf_projects = if cf_ids.is_empty() {
-        vec![]
-    } else {
-        CURSEFORGE_API.get_mods(cf_ids).await?
-    };
+    // Modrinth projects can be configured using their slugs, so they are cached using the configured ID
+    let mut mr_projects = Vec::new();
+    let mut to_fetch = Vec::new();
+    for id in &mr_ids {
+        if let Some(project) = cache.get::<Project>(&format!("modrinth/project/{id}")) {
+            mr_projects.push(project);
+        } else {
+            to_fetch.push(id.as_str());
+        }
+    }
+    if !to_fetch.is_empty() {
+        for project in retry_request(|| MODRINTH_API.get_multiple_projects(&to_fetch)).await? {
+            if let Some(id) = to_fetch
+                .iter()
+                .find(|id| **id == project.id || **id == project.slug)
+            {
+                cache.insert(format!("modrinth/project/{id}"), &project, None)?;
+            }
+            mr_projects.push(project);
+        }
+    }
 
-    let mut metadata = Vec::new();
-    for (project, members) in mr_projects.into_iter().zip(mr_teams_members) {
+    let to_fetch = mr_projects
+        .iter()
+        .map(|project| project.team.as_str())
+        .filter(|team| {
+            cache
+                .get::<Vec<TeamMember>>(&format!("modrinth/members/{team}"))
+                .is_none()
+        })
+        .collect_vec();
+    if !to_fetch.is_empty() {
+        for (team, members) in to_fetch
+            .iter()
+            .zip(retry_request(|| MODRINTH_API.list_multiple_teams_members(&to_fetch)).await?)
+        {
+            cache.insert(format!("modrinth/members/{team}"), &members, None)?;
+        }
+    }
+    for project in mr_projects {
+        let members = cache
+            .get(&format!("modrinth/members/{}", project.team))
+            .unwrap_or_default();
         metadata.push(Metadata::MD(project, members));
     }
-    for project in cf_projects {
-        metadata.push(Metadata::CF(project));
+
+    if !cf_ids.is_empty() {
+        for project in retry_request(|| CURSEFORGE_API.get_mods(cf_ids.clone())).await? {
+            cache.insert(format!("curseforge/mod/{}", project.id), &project, None)?;
+            metadata.push(Metadata::CF(project));
+        }
     }
+
     for res in tasks.join_all().await {
-        let (repo, releases) = res?;
-        metadata.push(Metadata::GH(repo, releases.items));
+        match res? {
+            (key, Conditional::NotModified) => cache.revalidate(&key)?,
+            (key, Conditional::Modified { value, etag }) => cache.insert(key, &value, etag)?,
+        }
+    }
+    for mod_ in &profile.mods {
+        if let ModIdentifier::GitHubRepository(owner, repo) = &mod_.identifier {
+            metadata.push(Metadata::GH(
+                cache
+                    .get(&format!("github/repo/{owner}/{repo}"))
+                    .context("Could not parse the metadata of a GitHub repository")?,
+                cache
+                    .get(&format!("github/releases/{owner}/{repo}"))
+                    .context("Could not parse the releases of a GitHub repository")?,
+            ));
+        }
+    }
+
+    cache.write()?;
+    Ok(metadata)
+}
+
+pub async fn verbose(profile: &mut Profile, markdown: bool, refresh: bool) -> Result<()> {
+    if !markdown {
+        eprint!("Querying metadata... ");
     }
+
+    let mut metadata = fetch_metadata(profile, refresh).await?;
     metadata.sort_unstable_by_key(|e| e.name().to_lowercase());
 
     if !markdown {
@@ -104,7 +298,7 @@ pub async fn verbose(profile: &mut Profile, markdown: bool) -> Result<()> {
         profile
             .mods
             .iter_mut()
-            .find(|mod_| mod_.identifier == project.id())
+            .find(|mod_| project.describes(&mod_.identifier))
             .context("Could not find expected mod")?
             .name = project.name().to_string();
 
@@ -126,6 +320,50 @@ pub async fn verbose(profile: &mut Profile, markdown: bool) -> Result<()> {
     Ok(())
 }
 
+/// Print the mods in `profile` as JSON, along with their metadata if `verbose` is true
+///
+/// If `refresh` is true, the metadata is fetched again instead of using the cached metadata.
+pub async fn json(
+    profile: &Profile,
+    settings: &ProfileSettings,
+    verbose: bool,
+    refresh: bool,
+) -> Result<()> {
+    let metadata = if verbose {
+        fetch_metadata(profile, refresh).await?
+    } else {
+        Vec::new()
+    };
+    let mut mods = profile
+        .mods
+        .iter()
+        .map(|mod_| {
+            let metadata = metadata
+                .iter()
+                .find(|project| project.describes(&mod_.identifier));
+            ListedMod {
+                name: metadata.map_or_else(|| mod_.name.clone(), |p| p.name().to_owned()),
+                platform: platform(&mod_.identifier),
+                identifier: mod_key(&mod_.identifier),
+                pinned_to: pinned_to(mod_, settings).map(ToOwned::to_owned),
+                details: metadata.map(Metadata::details),
+            }
+        })
+        .collect_vec();
+    mods.sort_unstable_by_key(|mod_| mod_.name.to_lowercase());
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&ListedProfile {
+            name: &profile.name,
+            mod_loader: profile.filters.mod_loader().map(ToString::to_string),
+            game_versions: profile.filters.game_versions().cloned().unwrap_or_default(),
+            mods,
+        })?
+    );
+    Ok(())
+}
+
 pub fn curseforge(project: &Mod) {
     println!(
         "
@@ -1,7 +1,15 @@
+pub mod cache;
 pub mod list;
 pub mod modpack;
+pub mod outdated;
+pub mod pin;
 pub mod profile;
 mod remove;
+pub mod rollback;
+mod search;
+mod tree;
 mod upgrade;
 pub use remove::remove;
-pub use upgrade::upgrade;
+pub use search::search;
+pub use tree::tree;
+pub use upgrade::{lock, resolve, upgrade};
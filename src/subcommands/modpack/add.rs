@@ -1,6 +1,13 @@
-use super::check_output_directory;
-use crate::{file_picker::pick_folder, TICK};
-use anyhow::{Context as _, Result};
+use super::{
+    check_output_directory, local_identifier,
+    upgrade::{read_index, ModpackIndex},
+};
+use crate::{
+    file_picker::pick_folder,
+    prompt::{can_prompt, required},
+    TICK,
+};
+use anyhow::{ensure, Context as _, Result};
 use colored::Colorize as _;
 use inquire::Confirm;
 use libium::{
@@ -9,7 +16,10 @@ use libium::{
     iter_ext::IterExt as _,
     modpack::add,
 };
-use std::path::PathBuf;
+use std::{
+    fs::canonicalize,
+    path::{Path, PathBuf},
+};
 
 pub async fn curseforge(
     config: &mut Config,
@@ -23,6 +33,7 @@ pub async fn curseforge(
     println!("Where should the modpack be installed to?");
     let output_dir = match output_dir {
         Some(some) => some,
+        None if !can_prompt() => return Err(required("the output directory", "`--output-dir`")),
         None => pick_folder(
             get_minecraft_dir(),
             "Pick an output directory",
@@ -33,6 +44,8 @@ pub async fn curseforge(
     check_output_directory(&output_dir)?;
     let install_overrides = match install_overrides {
         Some(some) => some,
+        // Overrides are installed by default
+        None if !can_prompt() => true,
         None => Confirm::new("Should overrides be installed?")
             .with_default(true)
             .prompt()
@@ -69,6 +82,7 @@ pub async fn modrinth(
     println!("Where should the modpack be installed to?");
     let output_dir = match output_dir {
         Some(some) => some,
+        None if !can_prompt() => return Err(required("the output directory", "`--output-dir`")),
         None => pick_folder(
             get_minecraft_dir(),
             "Pick an output directory",
@@ -79,6 +93,8 @@ pub async fn modrinth(
     check_output_directory(&output_dir)?;
     let install_overrides = match install_overrides {
         Some(some) => some,
+        // Overrides are installed by default
+        None if !can_prompt() => true,
         None => Confirm::new("Should overrides be installed?")
             .with_default(true)
             .prompt()
@@ -116,3 +132,61 @@ pub async fn modrinth(
     config.active_modpack = config.modpacks.len() - 1;
     Ok(())
 }
+
+/// Add the local modpack file at `path`, which is read from disk every time the modpack is upgraded
+pub fn local(
+    config: &mut Config,
+    path: &Path,
+    output_dir: Option<PathBuf>,
+    install_overrides: Option<bool>,
+) -> Result<()> {
+    eprint!("Checking modpack... ");
+    let path = canonicalize(path)?;
+    let name = match read_index(&path)? {
+        ModpackIndex::CurseForge(manifest) => manifest.name,
+        ModpackIndex::Modrinth(metadata) => metadata.name,
+    };
+    ensure!(
+        !config.modpacks.iter().any(|modpack| modpack.name == name),
+        "A modpack named {name} has already been added"
+    );
+    println!("{} ({name})", *TICK);
+    println!("Where should the modpack be installed to?");
+    let output_dir = match output_dir {
+        Some(some) => some,
+        None if !can_prompt() => return Err(required("the output directory", "`--output-dir`")),
+        None => pick_folder(
+            get_minecraft_dir(),
+            "Pick an output directory",
+            "Output Directory",
+        )?
+        .context("Please pick an output directory")?,
+    };
+    check_output_directory(&output_dir)?;
+    let install_overrides = match install_overrides {
+        Some(some) => some,
+        // Overrides are installed by default
+        None if !can_prompt() => true,
+        None => Confirm::new("Should overrides be installed?")
+            .with_default(true)
+            .prompt()
+            .unwrap_or_default(),
+    };
+    if install_overrides {
+        println!(
+            "{}",
+            "WARNING: Files in your output directory may be overwritten by modpack overrides"
+                .yellow()
+                .bold()
+        );
+    }
+    config.modpacks.push(Modpack {
+        name,
+        identifier: local_identifier(&path),
+        output_dir,
+        install_overrides,
+    });
+    // Make added modpack active
+    config.active_modpack = config.modpacks.len() - 1;
+    Ok(())
+}
@@ -1,5 +1,8 @@
 use super::check_output_directory;
-use crate::file_picker::pick_folder;
+use crate::{
+    file_picker::pick_folder,
+    prompt::{can_prompt, required},
+};
 use anyhow::Result;
 use colored::Colorize as _;
 use inquire::Confirm;
@@ -11,6 +14,12 @@ pub fn configure(
     output_dir: Option<PathBuf>,
     install_overrides: Option<bool>,
 ) -> Result<()> {
+    if output_dir.is_none() && install_overrides.is_none() && !can_prompt() {
+        return Err(required(
+            "the settings to change",
+            "`--output-dir` or `--install-overrides`",
+        ));
+    }
     match output_dir {
         Some(output_dir) => {
             check_output_directory(&output_dir)?;
@@ -29,6 +38,9 @@ pub fn configure(
     }
     modpack.install_overrides = if let Some(install_overrides) = install_overrides {
         install_overrides
+    } else if !can_prompt() {
+        // Settings that weren't provided are left unchanged
+        modpack.install_overrides
     } else {
         let install_overrides = Confirm::new("Should overrides be installed?")
             .with_default(modpack.install_overrides)
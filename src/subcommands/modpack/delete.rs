@@ -1,4 +1,8 @@
-use super::switch;
+use super::{local_file, switch};
+use crate::{
+    prompt::{can_prompt, required},
+    settings::Settings,
+};
 use anyhow::{Context as _, Result};
 use colored::Colorize as _;
 use inquire::Select;
@@ -8,8 +12,10 @@ use libium::{
 };
 use std::cmp::Ordering;
 
+/// Delete the modpack named `modpack_name`, or one picked by the user if not provided, along with its `settings`
 pub fn delete(
     config: &mut Config,
+    settings: &mut Settings,
     modpack_name: Option<String>,
     switch_to: Option<String>,
 ) -> Result<()> {
@@ -20,6 +26,8 @@ pub fn delete(
             .iter()
             .position(|modpack| modpack.name.eq_ignore_ascii_case(&modpack_name))
             .context("The modpack name provided does not exist")?
+    } else if !can_prompt() {
+        return Err(required("the modpack to delete", "its name"));
     } else {
         let modpack_names = config
             .modpacks
@@ -27,10 +35,12 @@ pub fn delete(
             .map(|modpack| {
                 format!(
                     "{} {}",
-                    match &modpack.identifier {
-                        ModpackIdentifier::CurseForgeModpack(id) =>
+                    match (&modpack.identifier, local_file(&modpack.identifier)) {
+                        (_, Some(path)) =>
+                            format!("{} {:8}", "LF".blue(), path.display().to_string().dimmed()),
+                        (ModpackIdentifier::CurseForgeModpack(id), None) =>
                             format!("{} {:8}", "CF".red(), id.to_string().dimmed()),
-                        ModpackIdentifier::ModrinthModpack(id) =>
+                        (ModpackIdentifier::ModrinthModpack(id), None) =>
                             format!("{} {:8}", "MR".green(), id.dimmed()),
                     },
                     modpack.name.bold(),
@@ -47,7 +57,19 @@ pub fn delete(
             return Ok(());
         }
     };
-    config.modpacks.remove(selection);
+    // The modpack to switch to cannot be picked, so check that it was provided before deleting anything
+    if selection == config.active_modpack
+        && config.modpacks.len() > 2
+        && switch_to.is_none()
+        && !can_prompt()
+    {
+        return Err(required(
+            "the modpack to switch to",
+            "its name using `--switch-to`",
+        ));
+    }
+    let modpack = config.modpacks.remove(selection);
+    settings.modpacks.remove(&modpack.name);
 
     match config.active_modpack.cmp(&selection) {
         // If the currently selected modpack is being removed
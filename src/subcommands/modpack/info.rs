@@ -1,3 +1,4 @@
+use super::local_file;
 use colored::Colorize as _;
 use libium::config::structs::{Modpack, ModpackIdentifier};
 
@@ -10,10 +11,15 @@ pub fn info(modpack: &Modpack, active: bool) {
         modpack.name.bold(),
         if active { " *" } else { "" },
         modpack.output_dir.display().to_string().blue().underline(),
-        match &modpack.identifier {
-            ModpackIdentifier::CurseForgeModpack(id) =>
+        match (&modpack.identifier, local_file(&modpack.identifier)) {
+            (_, Some(path)) => format!(
+                "{:10} {}",
+                "Local file".blue(),
+                path.display().to_string().dimmed()
+            ),
+            (ModpackIdentifier::CurseForgeModpack(id), None) =>
                 format!("{:10} {}", "CurseForge".red(), id.to_string().dimmed()),
-            ModpackIdentifier::ModrinthModpack(id) =>
+            (ModpackIdentifier::ModrinthModpack(id), None) =>
                 format!("{:10} {}", "Modrinth".green(), id.dimmed()),
         },
         modpack.install_overrides
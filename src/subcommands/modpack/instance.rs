@@ -0,0 +1,162 @@
+use crate::TICK;
+use anyhow::{bail, Context as _, Result};
+use colored::Colorize as _;
+use libium::config::structs::Modpack;
+use serde::Serialize;
+use std::{fs::write, path::Path};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MMCPack {
+    components: Vec<MMCComponent>,
+    format_version: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MMCComponent {
+    uid: &'static str,
+    version: String,
+    cached_name: &'static str,
+    cached_version: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    cached_volatile: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    dependency_only: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    important: bool,
+}
+
+impl MMCComponent {
+    fn new(uid: &'static str, cached_name: &'static str, version: String) -> Self {
+        Self {
+            uid,
+            cached_name,
+            cached_version: version.clone(),
+            version,
+            cached_volatile: false,
+            dependency_only: false,
+            important: false,
+        }
+    }
+}
+
+/// Get the Prism Launcher component UID and name of a dependency ID from a Modrinth modpack's metadata
+fn component_uid(dependency: &str) -> Option<(&'static str, &'static str)> {
+    match dependency {
+        "minecraft" => Some(("net.minecraft", "Minecraft")),
+        "fabric-loader" => Some(("net.fabricmc.fabric-loader", "Fabric Loader")),
+        "quilt-loader" => Some(("org.quiltmc.quilt-loader", "Quilt Loader")),
+        "forge" => Some(("net.minecraftforge", "Forge")),
+        "neoforge" => Some(("net.neoforged", "NeoForge")),
+        _ => None,
+    }
+}
+
+/// Convert a CurseForge manifest's Minecraft version and mod loader IDs (e.g. `forge-47.2.0`)
+/// into the dependency IDs and versions used by Modrinth modpacks
+pub fn curseforge_dependencies<'a>(
+    game_version: &str,
+    mod_loaders: impl IntoIterator<Item = &'a str>,
+) -> Vec<(String, String)> {
+    let mut dependencies = vec![("minecraft".to_owned(), game_version.to_owned())];
+    for mod_loader in mod_loaders {
+        if let Some((loader, version)) = mod_loader.split_once('-') {
+            dependencies.push((
+                match loader {
+                    "fabric" => "fabric-loader",
+                    "quilt" => "quilt-loader",
+                    loader => loader,
+                }
+                .to_owned(),
+                version.to_owned(),
+            ));
+        }
+    }
+    dependencies
+}
+
+/// Get the instance folder to write a Prism Launcher/MultiMC instance for `modpack` to
+///
+/// The modpack's output directory has to be the `.minecraft` or `minecraft` folder of the instance,
+/// so the instance folder is the one containing it.
+pub fn instance_dir(modpack: &Modpack) -> Result<&Path> {
+    match modpack.output_dir.file_name().and_then(|name| name.to_str()) {
+        Some(".minecraft" | "minecraft") => modpack
+            .output_dir
+            .parent()
+            .context("Unable to get the instance folder"),
+        _ => bail!(
+            "To create an instance, the modpack's output directory has to be the `.minecraft` folder inside the instance folder"
+        ),
+    }
+}
+
+/// Write a Prism Launcher/MultiMC instance named `name` to `instance_dir`,
+/// using the Minecraft and mod loader versions in `dependencies`
+///
+/// An existing `instance.cfg` is not overwritten so that settings changed in the launcher are kept.
+pub fn write_prism_instance(
+    instance_dir: &Path,
+    name: &str,
+    dependencies: &[(String, String)],
+) -> Result<()> {
+    let mut components = Vec::new();
+    for (dependency, version) in dependencies {
+        let (uid, name) = component_uid(dependency)
+            .with_context(|| format!("Unknown modpack dependency {dependency}"))?;
+        components.push(MMCComponent {
+            important: uid == "net.minecraft",
+            ..MMCComponent::new(uid, name, version.clone())
+        });
+    }
+    // Fabric and Quilt are loaded on top of the intermediary mappings for the Minecraft version
+    if let Some(minecraft) = dependencies
+        .iter()
+        .find(|(dependency, _)| dependency == "minecraft")
+        .map(|(_, version)| version)
+        .filter(|_| {
+            dependencies.iter().any(|(dependency, _)| {
+                dependency == "fabric-loader" || dependency == "quilt-loader"
+            })
+        })
+    {
+        components.push(MMCComponent {
+            cached_volatile: true,
+            dependency_only: true,
+            ..MMCComponent::new(
+                "net.fabricmc.intermediary",
+                "Intermediary Mappings",
+                minecraft.clone(),
+            )
+        });
+    }
+    // Minecraft has to be the first component and the intermediary mappings the second,
+    // for the mod loaders to be applied on top of them
+    components.sort_by_key(|component| match component.uid {
+        "net.minecraft" => 0,
+        "net.fabricmc.intermediary" => 1,
+        _ => 2,
+    });
+
+    write(
+        instance_dir.join("mmc-pack.json"),
+        serde_json::to_string_pretty(&MMCPack {
+            components,
+            format_version: 1,
+        })?,
+    )?;
+    if !instance_dir.join("instance.cfg").exists() {
+        write(
+            instance_dir.join("instance.cfg"),
+            format!("[General]\nInstanceType=OneSix\nname={name}\n"),
+        )?;
+    }
+
+    println!(
+        "{} Wrote a Prism Launcher instance to {}",
+        &*TICK,
+        instance_dir.display().to_string().blue().underline()
+    );
+    Ok(())
+}
@@ -2,6 +2,7 @@ pub mod add;
 mod configure;
 mod delete;
 mod info;
+mod instance;
 mod switch;
 mod upgrade;
 pub use configure::configure;
@@ -10,12 +11,34 @@ pub use info::info;
 pub use switch::switch;
 pub use upgrade::upgrade;
 
-use crate::file_picker::pick_folder;
+use crate::{file_picker::pick_folder, prompt};
 use anyhow::{ensure, Context as _, Result};
 use fs_extra::dir::{copy, CopyOptions};
 use inquire::Confirm;
-use libium::HOME;
-use std::{fs::read_dir, path::Path};
+use libium::{config::structs::ModpackIdentifier, HOME};
+use std::{
+    fs::read_dir,
+    path::{Path, PathBuf},
+};
+
+/// The prefix of the identifiers of local modpack files, which can't be part of a Modrinth project ID or slug
+const LOCAL_PREFIX: &str = "file://";
+
+/// Get the identifier of the local modpack file at `path`
+///
+/// Modpack identifiers can only refer to projects, so the path is stored in place of a Modrinth project ID
+/// behind a prefix that such IDs can't contain.
+pub fn local_identifier(path: &Path) -> ModpackIdentifier {
+    ModpackIdentifier::ModrinthModpack(format!("{LOCAL_PREFIX}{}", path.display()))
+}
+
+/// Get the path of the local modpack file that `identifier` refers to, if it refers to one
+pub fn local_file(identifier: &ModpackIdentifier) -> Option<PathBuf> {
+    match identifier {
+        ModpackIdentifier::ModrinthModpack(id) => id.strip_prefix(LOCAL_PREFIX).map(PathBuf::from),
+        ModpackIdentifier::CurseForgeModpack(_) => None,
+    }
+}
 
 pub fn check_output_directory(output_dir: &Path) -> Result<()> {
     ensure!(
@@ -39,9 +62,10 @@ pub fn check_output_directory(output_dir: &Path) -> Result<()> {
                 "There are files in the {} folder in your output directory, these will be deleted when you upgrade.",
                 check_dir.file_name().context("Unable to get folder name")?.to_string_lossy()
             );
-            if Confirm::new("Would like to create a backup?")
-                .prompt()
-                .unwrap_or_default()
+            if prompt::can_prompt()
+                && Confirm::new("Would like to create a backup?")
+                    .prompt()
+                    .unwrap_or_default()
             {
                 let backup_dir = pick_folder(
                     &*HOME,
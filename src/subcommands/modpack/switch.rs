@@ -1,3 +1,5 @@
+use super::local_file;
+use crate::prompt::{can_prompt, required};
 use anyhow::{anyhow, Result};
 use colored::Colorize as _;
 use inquire::Select;
@@ -22,6 +24,8 @@ pub fn switch(config: &mut Config, modpack_name: Option<String>) -> Result<()> {
             }
             None => Err(anyhow!("The modpack provided does not exist")),
         }
+    } else if !can_prompt() {
+        Err(required("the modpack to switch to", "its name"))
     } else {
         let modpack_info = config
             .modpacks
@@ -29,10 +33,12 @@ pub fn switch(config: &mut Config, modpack_name: Option<String>) -> Result<()> {
             .map(|modpack| {
                 format!(
                     "{} {}",
-                    match &modpack.identifier {
-                        ModpackIdentifier::CurseForgeModpack(id) =>
+                    match (&modpack.identifier, local_file(&modpack.identifier)) {
+                        (_, Some(path)) =>
+                            format!("{} {:8}", "LF".blue(), path.display().to_string().dimmed()),
+                        (ModpackIdentifier::CurseForgeModpack(id), None) =>
                             format!("{} {:8}", "CF".red(), id.to_string().dimmed()),
-                        ModpackIdentifier::ModrinthModpack(id) =>
+                        (ModpackIdentifier::ModrinthModpack(id), None) =>
                             format!("{} {:8}", "MR".green(), id.dimmed()),
                     },
                     modpack.name.bold(),
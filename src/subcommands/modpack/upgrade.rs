@@ -1,9 +1,20 @@
+use super::{
+    instance::{curseforge_dependencies, instance_dir, write_prism_instance},
+    local_file,
+};
 use crate::{
-    download::{clean, download, read_overrides},
+    cache,
+    download::{clean, download, plan_clean, print_downloads, read_overrides, Downloadable},
+    lockfile::{self, LockedModpack},
+    report::{Failure, Report, ResolvedFile},
+    retry::retry_request,
+    settings::{ModpackSettings, Side},
     STYLE_BYTE, TICK,
 };
-use anyhow::{Context as _, Result};
+use anyhow::{bail, ensure, Context as _, Result};
 use colored::Colorize as _;
+use ferinth::structures::project::ProjectSupportRange;
+use furse::structures::file_structs::HashAlgo;
 use indicatif::ProgressBar;
 use libium::{
     config::structs::{Modpack, ModpackIdentifier},
@@ -12,55 +23,372 @@ use libium::{
         curseforge::structs::Manifest as CFManifest, modrinth::structs::Metadata as MRMetadata,
         read_file_from_zip, zip_extract,
     },
-    upgrade::{from_modpack_file, try_from_cf_file, DistributionDeniedError, DownloadData},
-    CURSEFORGE_API, HOME,
+    upgrade::{from_modpack_file, try_from_cf_file, DistributionDeniedError},
+    CURSEFORGE_API, HOME, MODRINTH_API,
 };
+use reqwest::Url;
+use serde::Deserialize;
 use std::{
-    fs::File,
+    env::temp_dir,
+    ffi::OsString,
+    fs::{remove_file, write, File},
     io::BufReader,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
+    process,
     time::Duration,
 };
 use tokio::task::JoinSet;
+use zip::ZipArchive;
 
-pub async fn upgrade(modpack: &'_ Modpack) -> Result<()> {
-    let mut to_download: Vec<DownloadData> = Vec::new();
-    let mut to_install = Vec::new();
-    let install_msg;
+/// List the entries in the `overrides` folder of the modpack at `modpack_filepath` without extracting them
+///
+/// The paths returned are where [`zip_extract`] would extract the entries to in `tmp_dir`.
+fn list_overrides(
+    modpack_filepath: &Path,
+    overrides: &str,
+    tmp_dir: &Path,
+) -> Result<Vec<(OsString, PathBuf)>> {
+    let archive = ZipArchive::new(BufReader::new(File::open(modpack_filepath)?))?;
+    let mut to_install: Vec<(OsString, PathBuf)> = Vec::new();
+    for name in archive.file_names() {
+        if let Some(Component::Normal(entry)) = Path::new(name)
+            .strip_prefix(overrides)
+            .ok()
+            .and_then(|path| path.components().next())
+        {
+            if !to_install
+                .iter()
+                .any(|(installable, _)| installable == entry)
+            {
+                to_install.push((entry.to_owned(), tmp_dir.join(overrides).join(entry)));
+            }
+        }
+    }
+    Ok(to_install)
+}
 
-    let progress_bar = ProgressBar::new(0).with_style(STYLE_BYTE.clone());
-    let modpack_filepath = modpack
-        .identifier
-        .download_file(
-            |total| {
-                progress_bar.println("Downloading Modpack".bold().to_string());
-                progress_bar.enable_steady_tick(Duration::from_millis(100));
-                progress_bar.set_length(total as u64);
-            },
-            |additional| {
-                progress_bar.inc(additional as u64);
-            },
+/// Get the message telling the user how to play the CurseForge modpack with `manifest`
+fn curseforge_install_msg(manifest: &CFManifest) -> String {
+    format!(
+        "You can play this modpack using Minecraft {} with {}",
+        manifest.minecraft.version,
+        manifest
+            .minecraft
+            .mod_loaders
+            .iter()
+            .map(|this| &this.id)
+            .display(", ")
+    )
+}
+
+/// Get the overrides of the CurseForge modpack at `modpack_filepath` with `manifest` to install
+///
+/// The modpack is extracted to a temporary directory unless `dry_run` is true.
+fn curseforge_overrides(
+    modpack_filepath: &Path,
+    manifest: &CFManifest,
+    dry_run: bool,
+) -> Result<Vec<(OsString, PathBuf)>> {
+    let tmp_dir = HOME
+        .join(".config")
+        .join("ferium")
+        .join(".tmp")
+        .join(&manifest.name);
+    Ok(if dry_run {
+        list_overrides(modpack_filepath, &manifest.overrides, &tmp_dir)?
+    } else {
+        zip_extract(modpack_filepath, &tmp_dir)?;
+        read_overrides(&tmp_dir.join(&manifest.overrides))?
+    })
+}
+
+/// The environments a file in a Modrinth modpack is used in
+#[derive(Deserialize)]
+struct FileEnv {
+    server: ProjectSupportRange,
+}
+
+/// The index file of a modpack, which determines how it is installed
+pub(super) enum ModpackIndex {
+    CurseForge(CFManifest),
+    Modrinth(MRMetadata),
+}
+
+/// Read the index file of the modpack at `modpack_filepath`
+///
+/// The modpack's format is determined from the index file it contains rather than where it was obtained from.
+pub(super) fn read_index(modpack_filepath: &Path) -> Result<ModpackIndex> {
+    if let Some(manifest) = read_file_from_zip(
+        BufReader::new(File::open(modpack_filepath)?),
+        "manifest.json",
+    )? {
+        Ok(ModpackIndex::CurseForge(serde_json::from_str(&manifest)?))
+    } else if let Some(metadata) = read_file_from_zip(
+        BufReader::new(File::open(modpack_filepath)?),
+        "modrinth.index.json",
+    )? {
+        Ok(ModpackIndex::Modrinth(serde_json::from_str(&metadata)?))
+    } else {
+        bail!("The modpack does not contain a CurseForge manifest or a Modrinth metadata file")
+    }
+}
+
+/// A temporary file that is deleted when it is dropped
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = remove_file(&self.0);
+    }
+}
+
+/// Get the download URL of the latest file of the modpack with `identifier`
+async fn latest_file_url(identifier: &ModpackIdentifier) -> Result<Url> {
+    Ok(match identifier {
+        ModpackIdentifier::CurseForgeModpack(id) => {
+            retry_request(|| CURSEFORGE_API.get_mod_files(*id))
+                .await?
+                .into_iter()
+                .next()
+                .context("The modpack does not have any files")?
+                .download_url
+                .context("The modpack's author has denied third parties from downloading it")?
+        }
+        ModpackIdentifier::ModrinthModpack(id) => {
+            let mut files = retry_request(|| MODRINTH_API.list_versions(id))
+                .await?
+                .into_iter()
+                .next()
+                .context("The modpack does not have any versions")?
+                .files;
+            let primary = files.iter().position(|file| file.primary).unwrap_or(0);
+            ensure!(
+                primary < files.len(),
+                "The modpack's latest version does not have any files"
+            );
+            files.swap_remove(primary).url
+        }
+    })
+}
+
+/// Download the latest file of the modpack with `identifier` to a temporary file outside of ferium's directories
+///
+/// Unlike [`ModpackIdentifier::download_file`], the modpack is not saved to the cache.
+async fn download_temporarily(identifier: &ModpackIdentifier) -> Result<TempFile> {
+    eprint!("{}", "Downloading modpack... ".bold());
+    let url = latest_file_url(identifier).await?;
+    let bytes = retry_request(|| async {
+        reqwest::get(url.clone())
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await
+    })
+    .await?;
+    let temp_file = TempFile(temp_dir().join(format!("ferium-modpack-{}.zip", process::id())));
+    write(&temp_file.0, bytes)?;
+    eprintln!("{}", &*TICK);
+    Ok(temp_file)
+}
+
+/// Download and install the latest version of `modpack`
+///
+/// If `dry_run` is true, the changes that would be made to the output directory are printed instead,
+/// and the modpack is read from a temporary file that is deleted afterwards.
+/// Files that are not supported on the side in `settings` are not installed.
+/// Local modpack files are read from disk instead of downloading the modpack.
+/// If `instance` is true, a Prism Launcher/MultiMC instance is also written for the modpack.
+/// The files resolved for the modpack are recorded in the lockfile at `lockfile_path`,
+/// and if `offline` is true, they are installed from there and the download cache instead.
+/// If `report_file` is provided, a [`Report`] of what was resolved and changed is written to it as JSON,
+/// even if installing the modpack fails.
+pub async fn upgrade(
+    modpack: &'_ Modpack,
+    settings: &ModpackSettings,
+    dry_run: bool,
+    instance: bool,
+    lockfile_path: &Path,
+    offline: bool,
+    report_file: Option<&Path>,
+) -> Result<()> {
+    let side = settings.side;
+    let instance_dir = if instance {
+        Some(instance_dir(modpack)?)
+    } else {
+        None
+    };
+
+    if offline {
+        let locked = lockfile::read(lockfile_path)?
+            .modpacks
+            .remove(&modpack.name)
+            .with_context(|| {
+                format!(
+                    "{} has never been upgraded, so there are no files to install offline",
+                    modpack.name
+                )
+            })?;
+        ensure!(
+            locked.file.is_file(),
+            "The modpack file {} is no longer cached, upgrade while online to download it again",
+            locked.file.display()
+        );
+        let to_download = locked
+            .files
+            .into_iter()
+            .map(Downloadable::from)
+            .collect_vec();
+        let mut report = Report {
+            dry_run,
+            ..Report::default()
+        };
+        let result = install(
+            modpack,
+            &locked.file,
+            side,
+            dry_run,
+            instance_dir,
+            Some(to_download),
+            lockfile_path,
+            &mut report,
         )
-        .await?;
-    let modpack_file = File::open(&modpack_filepath)?;
-    progress_bar.finish_and_clear();
+        .await;
+        write_report(&report, report_file)?;
+        return result;
+    }
 
-    match &modpack.identifier {
-        ModpackIdentifier::CurseForgeModpack(_) => {
-            let manifest: CFManifest = serde_json::from_str(
-                &read_file_from_zip(BufReader::new(modpack_file), "manifest.json")?
-                    .context("Does not contain manifest")?,
-            )?;
+    // Nothing is saved to the cache during a dry run, the modpack is only kept until it has been read
+    let temp_file;
+    let modpack_filepath = if let Some(file) = local_file(&modpack.identifier) {
+        ensure!(
+            file.is_file(),
+            "The modpack file {} no longer exists",
+            file.display()
+        );
+        file
+    } else if dry_run {
+        temp_file = download_temporarily(&modpack.identifier).await?;
+        temp_file.0.clone()
+    } else {
+        let progress_bar = ProgressBar::new(0).with_style(STYLE_BYTE.clone());
+        let modpack_filepath = modpack
+            .identifier
+            .download_file(
+                |total| {
+                    progress_bar.println("Downloading Modpack".bold().to_string());
+                    progress_bar.enable_steady_tick(Duration::from_millis(100));
+                    progress_bar.set_length(total as u64);
+                },
+                |additional| {
+                    progress_bar.inc(additional as u64);
+                },
+            )
+            .await?;
+        progress_bar.finish_and_clear();
+        modpack_filepath
+    };
+
+    let mut report = Report {
+        dry_run,
+        ..Report::default()
+    };
+    // The report is written even if installing fails, so that it shows what was done before then
+    let result = install(
+        modpack,
+        &modpack_filepath,
+        side,
+        dry_run,
+        instance_dir,
+        None,
+        lockfile_path,
+        &mut report,
+    )
+    .await;
+    write_report(&report, report_file)?;
+    result
+}
 
+/// Write `report` to `report_file` if it is provided
+fn write_report(report: &Report, report_file: Option<&Path>) -> Result<()> {
+    if let Some(report_file) = report_file {
+        report.write_file(report_file)?;
+        println!(
+            "{} Wrote the report to {}",
+            &*TICK,
+            report_file.display().to_string().blue().underline()
+        );
+    }
+    Ok(())
+}
+
+/// Install the modpack file at `modpack_filepath` to `modpack`'s output directory
+///
+/// Files that are not supported on `side` are not installed.
+/// If `dry_run` is true, the changes that would be made to the output directory are printed instead.
+/// If `instance_dir` is provided, a Prism Launcher/MultiMC instance is also written to it.
+/// If `locked_files` are provided, they are installed from the output directory and the download cache
+/// instead of resolving the modpack's files, otherwise the resolved files are recorded in the lockfile at `lockfile_path`.
+/// What was resolved and changed is recorded in `report`.
+#[expect(
+    clippy::too_many_arguments,
+    reason = "`upgrade` passes most of its options through"
+)]
+async fn install(
+    modpack: &Modpack,
+    modpack_filepath: &Path,
+    side: Side,
+    dry_run: bool,
+    instance_dir: Option<&Path>,
+    locked_files: Option<Vec<Downloadable>>,
+    lockfile_path: &Path,
+    report: &mut Report,
+) -> Result<()> {
+    let offline = locked_files.is_some();
+    let mut to_download: Vec<Downloadable> = Vec::new();
+    let mut to_install = Vec::new();
+    let install_msg;
+    // The Minecraft and mod loader versions, using the dependency IDs of Modrinth modpacks
+    let dependencies;
+    let mut skipped = 0;
+
+    match read_index(modpack_filepath)? {
+        ModpackIndex::CurseForge(manifest) if offline => {
+            to_download = locked_files.unwrap_or_default();
+            install_msg = curseforge_install_msg(&manifest);
+            dependencies = curseforge_dependencies(
+                &manifest.minecraft.version,
+                manifest
+                    .minecraft
+                    .mod_loaders
+                    .iter()
+                    .map(|this| this.id.as_str()),
+            );
+            if modpack.install_overrides {
+                to_install = curseforge_overrides(modpack_filepath, &manifest, dry_run)?;
+            }
+        }
+        ModpackIndex::CurseForge(manifest) => {
             eprint!("\n{}", "Determining files to download... ".bold());
 
-            let file_ids = manifest.files.iter().map(|file| file.file_id).collect();
-            let files = CURSEFORGE_API.get_files(file_ids).await?;
+            let file_ids = manifest.files.iter().map(|file| file.file_id).collect_vec();
+            let files = retry_request(|| CURSEFORGE_API.get_files(file_ids.clone())).await?;
             println!("{} Fetched {} mods", &*TICK, files.len());
 
             let mut tasks = JoinSet::new();
             let mut msg_shown = false;
             for file in files {
+                if !side.allows_curseforge(&file.sortable_game_versions) {
+                    skipped += 1;
+                    continue;
+                }
+                let find_hash = |algo: fn(&HashAlgo) -> bool| {
+                    file.hashes
+                        .iter()
+                        .find(|hash| algo(&hash.algo))
+                        .map(|hash| hash.value.clone())
+                };
+                let sha1 = find_hash(|algo| matches!(algo, HashAlgo::Sha1));
+                let md5 = find_hash(|algo| matches!(algo, HashAlgo::Md5));
                 match try_from_cf_file(file) {
                     Ok((_, mut downloadable)) => {
                         downloadable.output = PathBuf::from(
@@ -74,7 +402,11 @@ pub async fn upgrade(modpack: &'_ Modpack) -> Result<()> {
                             },
                         )
                         .join(downloadable.filename());
-                        to_download.push(downloadable);
+                        to_download.push(Downloadable {
+                            sha1,
+                            md5,
+                            ..downloadable.into()
+                        });
                     }
                     Err(DistributionDeniedError(mod_id, file_id)) => {
                         if !msg_shown {
@@ -82,54 +414,58 @@ pub async fn upgrade(modpack: &'_ Modpack) -> Result<()> {
                         }
                         msg_shown = true;
                         tasks.spawn(async move {
-                            let project = CURSEFORGE_API.get_mod(mod_id).await?;
+                            let project = retry_request(|| CURSEFORGE_API.get_mod(mod_id)).await?;
+                            let url = format!("{}/download/{file_id}", project.links.website_url);
                             eprintln!(
                                 "- {}
                            \r  {}",
                                 project.name.bold(),
-                                format!("{}/download/{file_id}", project.links.website_url)
-                                    .blue()
-                                    .underline(),
+                                url.blue().underline(),
                             );
-                            Ok::<(), furse::Error>(())
+                            Ok::<_, anyhow::Error>(Failure {
+                                name: project.name,
+                                error: format!(
+                                    "Third parties are not allowed to download this file, download it manually from {url}"
+                                ),
+                            })
                         });
                     }
                 }
             }
 
             for res in tasks.join_all().await {
-                res?;
+                report.failures.push(res?);
             }
 
-            install_msg = format!(
-                "You can play this modpack using Minecraft {} with {}",
-                manifest.minecraft.version,
+            install_msg = curseforge_install_msg(&manifest);
+            dependencies = curseforge_dependencies(
+                &manifest.minecraft.version,
                 manifest
                     .minecraft
                     .mod_loaders
                     .iter()
-                    .map(|this| &this.id)
-                    .display(", ")
+                    .map(|this| this.id.as_str()),
             );
 
             if modpack.install_overrides {
-                let tmp_dir = HOME
-                    .join(".config")
-                    .join("ferium")
-                    .join(".tmp")
-                    .join(manifest.name);
-                zip_extract(&modpack_filepath, &tmp_dir)?;
-                to_install = read_overrides(&tmp_dir.join(manifest.overrides))?;
+                to_install = curseforge_overrides(modpack_filepath, &manifest, dry_run)?;
             }
         }
-        ModpackIdentifier::ModrinthModpack(_) => {
-            let metadata: MRMetadata = serde_json::from_str(
-                &read_file_from_zip(BufReader::new(modpack_file), "modrinth.index.json")?
-                    .context("Does not contain metadata file")?,
-            )?;
-
+        ModpackIndex::Modrinth(metadata) => {
             for file in metadata.files {
-                to_download.push(from_modpack_file(file));
+                let env: Option<FileEnv> =
+                    serde_json::from_value(serde_json::to_value(&file.env)?)?;
+                if env.is_some_and(|env| !side.allows_modrinth(&env.server)) {
+                    skipped += 1;
+                    continue;
+                }
+                let sha1 = file.hashes.sha1.clone();
+                let sha512 = file.hashes.sha512.clone();
+                to_download.push(Downloadable {
+                    sha1: Some(sha1),
+                    sha512: Some(sha512),
+                    ..from_modpack_file(file).into()
+                });
             }
 
             install_msg = format!(
@@ -140,6 +476,19 @@ pub async fn upgrade(modpack: &'_ Modpack) -> Result<()> {
                     .map(|this| format!("{:?} {}", this.0, this.1))
                     .display("\n")
             );
+            dependencies = metadata
+                .dependencies
+                .iter()
+                .map(|(id, version)| {
+                    Ok((
+                        serde_json::to_value(id)?
+                            .as_str()
+                            .context("Invalid modpack dependency ID")?
+                            .to_owned(),
+                        version.clone(),
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?;
 
             if modpack.install_overrides {
                 let tmp_dir = HOME
@@ -147,32 +496,95 @@ pub async fn upgrade(modpack: &'_ Modpack) -> Result<()> {
                     .join("ferium")
                     .join(".tmp")
                     .join(metadata.name);
-                zip_extract(&modpack_filepath, &tmp_dir)?;
-                to_install = read_overrides(&tmp_dir.join("overrides"))?;
+                to_install = if dry_run {
+                    list_overrides(modpack_filepath, "overrides", &tmp_dir)?
+                } else {
+                    zip_extract(modpack_filepath, &tmp_dir)?;
+                    read_overrides(&tmp_dir.join("overrides"))?
+                };
             }
         }
     }
-    clean(
-        &modpack.output_dir.join("mods"),
-        &mut to_download,
-        &mut Vec::new(),
-    )
-    .await?;
-    clean(
-        &modpack.output_dir.join("resourcepacks"),
-        &mut to_download,
-        &mut Vec::new(),
-    )
-    .await?;
-    // TODO: Check for `to_install` files that are already installed
-    if to_download.is_empty() && to_install.is_empty() {
-        println!("\n{}", "All up to date!".bold());
-    } else {
+    report.resolved = to_download.iter().map(ResolvedFile::from).collect_vec();
+    if offline {
+        cache::ensure_available(&modpack.output_dir, &to_download)?;
+    } else if !dry_run {
+        let mut lockfile = lockfile::read(lockfile_path)?;
+        lockfile.modpacks.insert(
+            modpack.name.clone(),
+            LockedModpack {
+                file: modpack_filepath.to_owned(),
+                files: to_download.iter().cloned().map(Into::into).collect_vec(),
+            },
+        );
+        lockfile::write_file(lockfile_path, &lockfile)?;
+    }
+    if skipped > 0 {
         println!(
-            "\n{}\n",
-            format!("Downloading {} Mod Files", to_download.len()).bold()
+            "{}",
+            format!("Skipped {skipped} files that are not supported on {side}s").yellow()
         );
-        download(modpack.output_dir.clone(), to_download, to_install).await?;
+    }
+    if dry_run {
+        println!("\n{}\n", "Dry Run".bold());
+        let mods_plan = plan_clean(
+            &modpack.output_dir.join("mods"),
+            &mut to_download,
+            &mut Vec::new(),
+        )?;
+        let resourcepacks_plan = plan_clean(
+            &modpack.output_dir.join("resourcepacks"),
+            &mut to_download,
+            &mut Vec::new(),
+        )?;
+        mods_plan.print();
+        resourcepacks_plan.print();
+        print_downloads(&to_download, &to_install);
+        if mods_plan.is_empty()
+            && resourcepacks_plan.is_empty()
+            && to_download.is_empty()
+            && to_install.is_empty()
+        {
+            println!("{}", "All up to date!".bold());
+        }
+        report.record_clean(&mods_plan);
+        report.record_clean(&resourcepacks_plan);
+        report.record_planned_downloads(&to_download);
+        report.record_installs(&to_install);
+    } else {
+        let mods_plan = clean(
+            &modpack.output_dir.join("mods"),
+            &mut to_download,
+            &mut Vec::new(),
+        )
+        .await?;
+        report.record_clean(&mods_plan);
+        let resourcepacks_plan = clean(
+            &modpack.output_dir.join("resourcepacks"),
+            &mut to_download,
+            &mut Vec::new(),
+        )
+        .await?;
+        report.record_clean(&resourcepacks_plan);
+        // TODO: Check for `to_install` files that are already installed
+        if to_download.is_empty() && to_install.is_empty() {
+            println!("\n{}", "All up to date!".bold());
+        } else {
+            println!(
+                "\n{}\n",
+                format!("Downloading {} Mod Files", to_download.len()).bold()
+            );
+            report.record_installs(&to_install);
+            let (downloaded, failures) =
+                download(modpack.output_dir.clone(), to_download, to_install).await?;
+            report.downloaded = downloaded;
+            let failed = !failures.is_empty();
+            report.failures.extend(failures);
+            ensure!(!failed, "\nCould not download some files");
+        }
+        if let Some(instance_dir) = instance_dir {
+            write_prism_instance(instance_dir, &modpack.name, &dependencies)?;
+        }
     }
     println!("\n{}", install_msg.bold());
     Ok(())
@@ -0,0 +1,236 @@
+use super::{
+    pin::{newer_versions, pinned_to},
+    resolve,
+};
+use crate::{
+    lockfile::{self, platform, LockedFile},
+    retry::retry_request,
+    settings::ProfileSettings,
+};
+use anyhow::{bail, Result};
+use colored::Colorize as _;
+use ferinth::structures::version::VersionType;
+use furse::structures::file_structs::FileReleaseType;
+use libium::{
+    config::structs::{ModIdentifier, Profile},
+    iter_ext::IterExt as _,
+    CURSEFORGE_API, GITHUB_API, MODRINTH_API,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fs::read_dir,
+    path::Path,
+};
+
+/// The exit code used when some mods have updates available
+pub const UPDATES_AVAILABLE_EXIT_CODE: u8 = 2;
+
+/// Returned when some mods have updates available, so that ferium can exit with [`UPDATES_AVAILABLE_EXIT_CODE`]
+#[derive(Debug)]
+pub struct UpdatesAvailable(pub usize);
+
+impl std::fmt::Display for UpdatesAvailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} mods have updates available", self.0)
+    }
+}
+
+impl std::error::Error for UpdatesAvailable {}
+
+/// A mod whose resolved file is not in the output directory
+struct Outdated<'a> {
+    file: &'a LockedFile,
+    installed: Option<&'a str>,
+}
+
+/// Get the release channel of the `files`, keyed by their file IDs
+///
+/// Uses a maximum of 2 network requests for Modrinth and CurseForge files, and one for each GitHub release.
+async fn release_channels(files: &[&LockedFile]) -> Result<HashMap<String, &'static str>> {
+    let mut channels = HashMap::new();
+
+    let mr_ids = files
+        .iter()
+        .filter(|file| {
+            matches!(
+                file.project,
+                ModIdentifier::ModrinthProject(_) | ModIdentifier::PinnedModrinthProject(..)
+            )
+        })
+        .map(|file| file.file_id.as_str())
+        .collect_vec();
+    if !mr_ids.is_empty() {
+        for version in retry_request(|| MODRINTH_API.get_multiple_versions(&mr_ids)).await? {
+            channels.insert(
+                version.id,
+                match version.version_type {
+                    VersionType::Release => "release",
+                    VersionType::Beta => "beta",
+                    VersionType::Alpha => "alpha",
+                },
+            );
+        }
+    }
+
+    let cf_ids = files
+        .iter()
+        .filter(|file| matches!(file.project, ModIdentifier::CurseForgeProject(_)))
+        .filter_map(|file| file.file_id.parse().ok())
+        .collect_vec();
+    if !cf_ids.is_empty() {
+        for file in retry_request(|| CURSEFORGE_API.get_files(cf_ids.clone())).await? {
+            channels.insert(
+                file.id.to_string(),
+                match file.release_type {
+                    FileReleaseType::Release => "release",
+                    FileReleaseType::Beta => "beta",
+                    FileReleaseType::Alpha => "alpha",
+                },
+            );
+        }
+    }
+
+    for file in files {
+        if let ModIdentifier::GitHubRepository(owner, repo) = &file.project {
+            let release = retry_request(|| async move {
+                GITHUB_API
+                    .repos(owner, repo)
+                    .releases()
+                    .get_by_tag(&file.file_id)
+                    .await
+            })
+            .await?;
+            channels.insert(
+                file.file_id.clone(),
+                if release.prerelease {
+                    "pre-release"
+                } else {
+                    "release"
+                },
+            );
+        }
+    }
+
+    Ok(channels)
+}
+
+/// Resolve the latest compatible files for `profile` like an upgrade would,
+/// and print the ones that are not in the output directory
+///
+/// The installed file of a mod is determined using the lockfile at `lockfile_path`.
+/// Nothing in the output directory or the lockfile is changed.
+/// Returns [`UpdatesAvailable`] as an error if some mods have updates available.
+pub async fn outdated(
+    profile: &Profile,
+    settings: &mut ProfileSettings,
+    lockfile_path: &Path,
+) -> Result<()> {
+    let resolution = resolve(profile, settings).await?;
+    let error = resolution.error();
+    let resolved = resolution.locked;
+
+    let mut installed = HashSet::new();
+    if profile.output_dir.exists() {
+        for entry in read_dir(&profile.output_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                installed.insert(entry.file_name());
+            }
+        }
+    }
+    let locked = lockfile::read(lockfile_path)?
+        .profiles
+        .remove(&profile.name)
+        .unwrap_or_default();
+
+    let outdated = resolved
+        .iter()
+        .filter(|file| !installed.contains(&OsString::from(&file.filename)))
+        .map(|file| Outdated {
+            file,
+            installed: locked
+                .iter()
+                .find(|locked| {
+                    locked.project == file.project
+                        && installed.contains(&OsString::from(&locked.filename))
+                })
+                .map(|locked| locked.filename.as_str()),
+        })
+        .collect_vec();
+
+    if outdated.is_empty() {
+        println!("\n{}", "All up to date!".bold());
+    } else {
+        let channels =
+            release_channels(&outdated.iter().map(|outdated| outdated.file).collect_vec()).await?;
+
+        let rows = outdated
+            .iter()
+            .map(|outdated| {
+                [
+                    outdated.file.name.as_str(),
+                    outdated.installed.unwrap_or("-"),
+                    outdated.file.filename.as_str(),
+                    platform(&outdated.file.project),
+                    channels.get(&outdated.file.file_id).copied().unwrap_or("-"),
+                ]
+            })
+            .collect_vec();
+        let header = ["Mod", "Installed", "Available", "Platform", "Channel"];
+        let widths: [usize; 5] = std::array::from_fn(|i| {
+            rows.iter()
+                .map(|row| row[i].len())
+                .chain([header[i].len()])
+                .max()
+                .unwrap_or_default()
+        });
+
+        println!(
+            "\n{}",
+            header
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .display("  ")
+                .to_string()
+                .bold()
+        );
+        for [name, installed, available, platform, channel] in rows {
+            println!(
+                "{}  {}  {}  {}  {}",
+                format!("{name:width$}", width = widths[0]).bold(),
+                format!("{installed:width$}", width = widths[1]).dimmed(),
+                format!("{available:width$}", width = widths[2]).green(),
+                format!("{platform:width$}", width = widths[3]),
+                format!("{channel:width$}", width = widths[4]).yellow(),
+            );
+        }
+    }
+
+    if profile
+        .mods
+        .iter()
+        .any(|mod_| pinned_to(mod_, settings).is_some())
+    {
+        let mut newer = newer_versions(profile, settings)
+            .await
+            .into_iter()
+            .collect_vec();
+        if !newer.is_empty() {
+            newer.sort_unstable_by_key(|(name, _)| name.to_lowercase());
+            println!("\n{}", "Pinned mods with newer versions".bold());
+            for (name, filename) in newer {
+                println!("{}  {}", name.bold(), filename.yellow());
+            }
+        }
+    }
+
+    if error {
+        bail!("\nCould not get the latest compatible version of some mods");
+    }
+    if !outdated.is_empty() {
+        bail!(UpdatesAvailable(outdated.len()));
+    }
+    Ok(())
+}
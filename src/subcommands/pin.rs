@@ -0,0 +1,304 @@
+use crate::{
+    lockfile::FileId,
+    prompt::{can_prompt, required},
+    retry::retry_request,
+    settings::{pin_key, ProfileSettings},
+    subcommands::upgrade::github_asset,
+    TICK,
+};
+use anyhow::{bail, ensure, Context as _, Result};
+use colored::Colorize as _;
+use inquire::Select;
+use libium::{
+    config::{
+        filters::ProfileParameters as _,
+        structs::{Mod, ModIdentifier, Profile},
+    },
+    iter_ext::IterExt as _,
+    CURSEFORGE_API, GITHUB_API, MODRINTH_API,
+};
+use std::collections::HashMap;
+use tokio::task::JoinSet;
+
+/// A version that a mod can be pinned to
+struct PinVersion {
+    /// The Modrinth version ID, CurseForge file ID, or GitHub release tag
+    id: String,
+    label: String,
+}
+
+impl std::fmt::Display for PinVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.label)
+    }
+}
+
+/// Get the mod in `profile` whose name or ID matches `query`
+fn find_mod<'a>(profile: &'a mut Profile, query: &str) -> Result<&'a mut Mod> {
+    profile
+        .mods
+        .iter_mut()
+        .find(|mod_| {
+            mod_.name.eq_ignore_ascii_case(query)
+                || match &mod_.identifier {
+                    ModIdentifier::CurseForgeProject(id) => id.to_string() == query,
+                    ModIdentifier::ModrinthProject(id)
+                    | ModIdentifier::PinnedModrinthProject(id, _) => id == query,
+                    ModIdentifier::GitHubRepository(owner, name) => {
+                        format!("{owner}/{name}").eq_ignore_ascii_case(query)
+                    }
+                    _ => false,
+                }
+        })
+        .with_context(|| format!("A mod with ID or name {query} is not present in this profile"))
+}
+
+/// Get what `mod_` is pinned to, i.e. a Modrinth version ID, CurseForge file ID, or GitHub release tag
+pub fn pinned_to<'a>(mod_: &'a Mod, settings: &'a ProfileSettings) -> Option<&'a str> {
+    match &mod_.identifier {
+        ModIdentifier::PinnedModrinthProject(_, version_id) => Some(version_id),
+        identifier => settings.pin(identifier).map(String::as_str),
+    }
+}
+
+/// Get the versions of the mod with `identifier` that are compatible with `profile`, newest first
+async fn compatible_versions(
+    identifier: &ModIdentifier,
+    profile: &Profile,
+) -> Result<Vec<PinVersion>> {
+    let game_versions = profile.filters.game_versions().cloned().unwrap_or_default();
+    let loader = profile
+        .filters
+        .mod_loader()
+        .map(|loader| loader.to_string().to_lowercase());
+
+    Ok(match identifier {
+        ModIdentifier::ModrinthProject(id) | ModIdentifier::PinnedModrinthProject(id, _) => {
+            retry_request(|| MODRINTH_API.list_versions(id))
+                .await?
+                .into_iter()
+                .filter(|version| {
+                    (game_versions.is_empty()
+                        || version
+                            .game_versions
+                            .iter()
+                            .any(|v| game_versions.contains(v)))
+                        && loader
+                            .as_ref()
+                            .map_or(true, |loader| version.loaders.iter().any(|l| l == loader))
+                })
+                .map(|version| PinVersion {
+                    label: format!("{:24}  {}", version.version_number, version.name),
+                    id: version.id,
+                })
+                .collect_vec()
+        }
+        ModIdentifier::CurseForgeProject(id) => {
+            let mut files = retry_request(|| CURSEFORGE_API.get_mod_files(*id))
+                .await?
+                .into_iter()
+                .filter(|file| {
+                    (game_versions.is_empty()
+                        || file.game_versions.iter().any(|v| game_versions.contains(v)))
+                        && loader.as_ref().map_or(true, |loader| {
+                            file.game_versions
+                                .iter()
+                                .any(|v| v.eq_ignore_ascii_case(loader))
+                        })
+                })
+                .collect_vec();
+            files.sort_unstable_by_key(|file| std::cmp::Reverse(file.file_date));
+            files
+                .into_iter()
+                .map(|file| PinVersion {
+                    id: file.id.to_string(),
+                    label: file.display_name,
+                })
+                .collect_vec()
+        }
+        ModIdentifier::GitHubRepository(owner, repo) => retry_request(|| async move {
+            GITHUB_API
+                .repos(owner, repo)
+                .releases()
+                .list()
+                .per_page(100)
+                .send()
+                .await
+        })
+        .await?
+        .items
+        .into_iter()
+        .map(|release| PinVersion {
+            label: format!(
+                "{:24}  {}",
+                release.tag_name,
+                release.name.unwrap_or_default()
+            ),
+            id: release.tag_name,
+        })
+        .collect_vec(),
+        _ => bail!("This mod cannot be pinned"),
+    })
+}
+
+/// Pin the mod in `profile` matching `query` to `version`, or a version picked by the user if not provided
+///
+/// `version` can be a Modrinth version ID or number, a CurseForge file ID, or a GitHub release tag.
+pub async fn pin(
+    profile: &mut Profile,
+    settings: &mut ProfileSettings,
+    query: &str,
+    version: Option<String>,
+) -> Result<()> {
+    let identifier = find_mod(profile, query)?.identifier.clone();
+
+    let version_id = match version {
+        Some(version) => match &identifier {
+            ModIdentifier::ModrinthProject(id) | ModIdentifier::PinnedModrinthProject(id, _) => {
+                retry_request(|| MODRINTH_API.list_versions(id))
+                    .await?
+                    .into_iter()
+                    .find(|v| v.id == version || v.version_number == version)
+                    .with_context(|| format!("There is no version {version} of {query}"))?
+                    .id
+            }
+            ModIdentifier::CurseForgeProject(id) => {
+                let file_id = version.parse::<i32>().with_context(|| {
+                    format!("CurseForge mods are pinned using a file ID, which {version} is not")
+                })?;
+                retry_request(|| CURSEFORGE_API.get_mod_file(*id, file_id))
+                    .await
+                    .with_context(|| format!("There is no file {version} of {query}"))?
+                    .id
+                    .to_string()
+            }
+            ModIdentifier::GitHubRepository(owner, repo) => {
+                let tag = version.as_str();
+                let assets = retry_request(|| async move {
+                    GITHUB_API
+                        .repos(owner, repo)
+                        .releases()
+                        .get_by_tag(tag)
+                        .await
+                })
+                .await
+                .with_context(|| format!("There is no release {version} of {query}"))?
+                .assets;
+                ensure!(
+                    github_asset(
+                        assets,
+                        profile.filters.game_versions().map_or(&[][..], Vec::as_slice),
+                        profile.filters.mod_loader(),
+                    )
+                    .is_some(),
+                    "The release {version} of {query} does not have any JAR files compatible with this profile"
+                );
+                version
+            }
+            _ => version,
+        },
+        None => {
+            if !can_prompt() {
+                return Err(required(
+                    &format!("the version to pin {query} to"),
+                    "it after the mod's name",
+                ));
+            }
+            let versions = compatible_versions(&identifier, profile).await?;
+            ensure!(
+                !versions.is_empty(),
+                "There are no versions of {query} compatible with this profile"
+            );
+            Select::new(&format!("Select the version to pin {query} to"), versions)
+                .prompt()?
+                .id
+        }
+    };
+
+    let mod_ = find_mod(profile, query)?;
+    match &identifier {
+        ModIdentifier::ModrinthProject(id) | ModIdentifier::PinnedModrinthProject(id, _) => {
+            mod_.identifier = ModIdentifier::PinnedModrinthProject(id.clone(), version_id.clone());
+        }
+        identifier => {
+            settings.pins.insert(
+                pin_key(identifier).context("This mod cannot be pinned")?,
+                version_id.clone(),
+            );
+        }
+    }
+    println!(
+        "{} Pinned {} to {}",
+        &*TICK,
+        mod_.name.bold(),
+        version_id.green()
+    );
+    Ok(())
+}
+
+/// Unpin the mod in `profile` matching `query`, so that it is upgraded to the latest compatible version again
+pub fn unpin(profile: &mut Profile, settings: &mut ProfileSettings, query: &str) -> Result<()> {
+    let mod_ = find_mod(profile, query)?;
+    match &mod_.identifier {
+        ModIdentifier::PinnedModrinthProject(id, _) => {
+            mod_.identifier = ModIdentifier::ModrinthProject(id.clone());
+        }
+        identifier => {
+            ensure!(
+                pin_key(identifier).is_some_and(|key| settings.pins.remove(&key).is_some()),
+                "{} is not pinned",
+                mod_.name
+            );
+        }
+    }
+    println!("{} Unpinned {}", &*TICK, mod_.name.bold());
+    Ok(())
+}
+
+/// Get the latest compatible file of the pinned mods in `profile` that are not pinned to it, keyed by the mod's name
+///
+/// Mods whose latest compatible file could not be fetched are left out with a warning,
+/// as no newer version is known for them.
+pub async fn newer_versions(
+    profile: &Profile,
+    settings: &ProfileSettings,
+) -> HashMap<String, String> {
+    let mut tasks = JoinSet::new();
+    for mod_ in &profile.mods {
+        let Some(pinned_to) = pinned_to(mod_, settings) else {
+            continue;
+        };
+        let pinned_to = pinned_to.to_owned();
+        let mut latest = mod_.clone();
+        if let ModIdentifier::PinnedModrinthProject(id, _) = &mod_.identifier {
+            latest.identifier = ModIdentifier::ModrinthProject(id.clone());
+        }
+        let filters = profile.filters.clone();
+        tasks.spawn(async move {
+            let download_file = retry_request(|| latest.fetch_download_file(filters.clone()))
+                .await
+                .map_err(|err: anyhow::Error| (latest.name.clone(), err))?;
+            let file_id = FileId::from_url(&download_file.download_url).map(|id| id.to_string());
+            Ok::<_, (String, anyhow::Error)>(
+                (file_id.as_deref() != Some(&pinned_to))
+                    .then(|| (latest.name, download_file.filename())),
+            )
+        });
+    }
+
+    let mut newer = HashMap::new();
+    for result in tasks.join_all().await {
+        match result {
+            Ok(Some((name, filename))) => {
+                newer.insert(name, filename);
+            }
+            Ok(None) => (),
+            Err((name, err)) => println!(
+                "{} Could not check {} for newer versions: {err}",
+                "Warning:".bold().yellow(),
+                name.bold()
+            ),
+        }
+    }
+    newer
+}
@@ -1,5 +1,8 @@
 use super::{check_output_directory, pick_minecraft_versions, pick_mod_loader};
-use crate::file_picker::pick_folder;
+use crate::{
+    file_picker::pick_folder,
+    prompt::{can_prompt, required},
+};
 use anyhow::{Context as _, Result};
 use inquire::{Select, Text};
 use libium::{
@@ -43,6 +46,9 @@ pub async fn configure(
     }
 
     if interactive {
+        if !can_prompt() {
+            return Err(required("the settings to change", "them as options"));
+        }
         let items = vec![
             // Show a file dialog
             "Mods output directory",
@@ -1,5 +1,8 @@
 use super::{check_output_directory, pick_minecraft_versions, pick_mod_loader};
-use crate::file_picker::pick_folder;
+use crate::{
+    file_picker::pick_folder,
+    prompt::{can_prompt, required},
+};
 use anyhow::{bail, ensure, Context as _, Result};
 use colored::Colorize as _;
 use inquire::{
@@ -39,6 +42,12 @@ pub async fn create(
             Profile::new(name, output_dir, game_versions, mod_loader)
         }
         (None, None, None, None) => {
+            if !can_prompt() {
+                return Err(required(
+                    "the profile's settings",
+                    "the name, game version, and mod loader options",
+                ));
+            }
             let mut selected_mods_dir = get_minecraft_dir().join("mods");
             println!(
                 "The default mods directory is {}",
@@ -97,6 +106,11 @@ pub async fn create(
                 .position(|profile| profile.name.eq_ignore_ascii_case(&profile_name))
                 .context("The profile name provided does not exist")?;
             profile.mods.clone_from(&config.profiles[selection].mods);
+        } else if !can_prompt() {
+            return Err(required(
+                "the profile to import mods from",
+                "its name using `--import <name>`",
+            ));
         } else {
             let profile_names = config
                 .profiles
@@ -1,4 +1,8 @@
 use super::switch;
+use crate::{
+    prompt::{can_prompt, required},
+    settings::Settings,
+};
 use anyhow::{Context as _, Result};
 use colored::Colorize as _;
 use inquire::Select;
@@ -8,8 +12,10 @@ use libium::{
 };
 use std::cmp::Ordering;
 
+/// Delete the profile named `profile_name`, or one picked by the user if not provided, along with its `settings`
 pub fn delete(
     config: &mut Config,
+    settings: &mut Settings,
     profile_name: Option<String>,
     switch_to: Option<String>,
 ) -> Result<()> {
@@ -20,6 +26,8 @@ pub fn delete(
             .iter()
             .position(|profile| profile.name.eq_ignore_ascii_case(&profile_name))
             .context("The profile name provided does not exist")?
+    } else if !can_prompt() {
+        return Err(required("the profile to delete", "its name"));
     } else {
         let profile_names = config
             .profiles
@@ -54,7 +62,19 @@ pub fn delete(
             return Ok(());
         }
     };
-    config.profiles.remove(selection);
+    // The profile to switch to cannot be picked, so check that it was provided before deleting anything
+    if selection == config.active_profile
+        && config.profiles.len() > 2
+        && switch_to.is_none()
+        && !can_prompt()
+    {
+        return Err(required(
+            "the profile to switch to",
+            "its name using `--switch-to`",
+        ));
+    }
+    let profile = config.profiles.remove(selection);
+    settings.profiles.remove(&profile.name);
 
     match config.active_profile.cmp(&selection) {
         // If the currently selected profile is being removed
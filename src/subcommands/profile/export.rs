@@ -0,0 +1,367 @@
+use crate::{
+    download::{download, Downloadable},
+    lockfile::LockedFile,
+    settings::ProfileSettings,
+    subcommands::resolve,
+    TICK,
+};
+use anyhow::{bail, ensure, Context as _, Result};
+use colored::Colorize as _;
+use libium::{
+    config::{
+        filters::ProfileParameters as _,
+        structs::{ModIdentifier, ModLoader, Profile},
+    },
+    iter_ext::IterExt as _,
+    HOME,
+};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest as _, Sha1};
+use sha2::Sha512;
+use std::{
+    collections::BTreeMap,
+    fs::{read, read_dir, remove_dir_all, File},
+    io::Write as _,
+    path::{Path, PathBuf},
+};
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+/// Modrinth only allows modpacks to download files from these domains
+const MRPACK_ALLOWED_HOSTS: [&str; 4] = [
+    "cdn.modrinth.com",
+    "github.com",
+    "raw.githubusercontent.com",
+    "gitlab.com",
+];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MRIndex {
+    format_version: usize,
+    game: &'static str,
+    version_id: String,
+    name: String,
+    files: Vec<MRIndexFile>,
+    dependencies: BTreeMap<&'static str, String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MRIndexFile {
+    path: String,
+    hashes: BTreeMap<&'static str, String>,
+    downloads: Vec<String>,
+    file_size: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CFManifest {
+    minecraft: CFMinecraft,
+    manifest_type: &'static str,
+    manifest_version: usize,
+    name: String,
+    version: String,
+    author: String,
+    files: Vec<CFManifestFile>,
+    overrides: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CFMinecraft {
+    version: String,
+    mod_loaders: Vec<CFModLoader>,
+}
+
+#[derive(Serialize)]
+struct CFModLoader {
+    id: String,
+    primary: bool,
+}
+
+#[derive(Serialize)]
+struct CFManifestFile {
+    #[serde(rename = "projectID")]
+    project_id: i32,
+    #[serde(rename = "fileID")]
+    file_id: i32,
+    required: bool,
+}
+
+#[derive(Deserialize)]
+struct LoaderVersion {
+    loader: LoaderVersionInner,
+}
+
+#[derive(Deserialize)]
+struct LoaderVersionInner {
+    version: String,
+}
+
+/// Get the latest version of `mod_loader` for `game_version` from the loader's metadata server
+///
+/// Only Fabric and Quilt provide such a server, the loader version has to be provided manually for the others.
+async fn latest_loader_version(mod_loader: &ModLoader, game_version: &str) -> Result<String> {
+    let url = match mod_loader {
+        ModLoader::Fabric => format!("https://meta.fabricmc.net/v2/versions/loader/{game_version}"),
+        ModLoader::Quilt => format!("https://meta.quiltmc.org/v3/versions/loader/{game_version}"),
+        ModLoader::Forge | ModLoader::NeoForge => {
+            bail!("Provide the {mod_loader} version to use with the `--loader-version` option")
+        }
+    };
+    let versions: Vec<LoaderVersion> =
+        serde_json::from_str(&reqwest::get(url).await?.error_for_status()?.text().await?)?;
+    Ok(versions
+        .into_iter()
+        .next()
+        .with_context(|| {
+            format!("There are no {mod_loader} versions for Minecraft {game_version}")
+        })?
+        .loader
+        .version)
+}
+
+/// Get the SHA-1 and SHA-512 hashes of the file at `path`
+fn hash_file(path: &Path) -> Result<(String, String)> {
+    let contents = read(path)?;
+    Ok((
+        format!("{:x}", Sha1::digest(&contents)),
+        format!("{:x}", Sha512::digest(&contents)),
+    ))
+}
+
+/// Recursively add the contents of `directory` to `zip`, under `prefix`
+fn add_directory(
+    zip: &mut ZipWriter<File>,
+    directory: &Path,
+    prefix: &str,
+    options: SimpleFileOptions,
+) -> Result<()> {
+    for entry in read_dir(directory)? {
+        let entry = entry?;
+        let name = format!("{prefix}/{}", entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            add_directory(zip, &entry.path(), &name, options)?;
+        } else {
+            zip.start_file(name, options)?;
+            zip.write_all(&read(entry.path())?)?;
+        }
+    }
+    Ok(())
+}
+
+/// Get the Minecraft version, mod loader, and mod loader version to export `profile` with
+///
+/// If `loader_version` is not provided, the latest version of the mod loader is used.
+async fn game_and_loader(
+    profile: &Profile,
+    loader_version: Option<String>,
+) -> Result<(String, &ModLoader, String)> {
+    let game_version = profile
+        .filters
+        .game_versions()
+        .and_then(|versions| versions.first())
+        .context("The profile does not filter by game version, so the Minecraft version cannot be determined")?;
+    let mod_loader = profile.filters.mod_loader().context(
+        "The profile does not filter by mod loader, so the mod loader cannot be determined",
+    )?;
+    let loader_version = match loader_version {
+        Some(version) => version,
+        None => latest_loader_version(mod_loader, game_version).await?,
+    };
+    Ok((game_version.clone(), mod_loader, loader_version))
+}
+
+/// Resolve the latest compatible files for `profile`, and download the ones matching `should_download` to a temporary directory
+async fn resolve_and_download(
+    profile: &Profile,
+    settings: &mut ProfileSettings,
+    should_download: impl Fn(&LockedFile) -> bool,
+) -> Result<(Vec<LockedFile>, PathBuf)> {
+    let resolution = resolve(profile, settings).await?;
+    ensure!(
+        !resolution.error(),
+        "\nCould not get the latest compatible version of some mods, the profile was not exported"
+    );
+
+    let tmp_dir = HOME
+        .join(".config")
+        .join("ferium")
+        .join(".tmp")
+        .join("export")
+        .join(&profile.name);
+    if tmp_dir.exists() {
+        remove_dir_all(&tmp_dir)?;
+    }
+    let locked = resolution.locked;
+    let to_download = locked
+        .iter()
+        .filter(|file| should_download(file))
+        .cloned()
+        .map(Downloadable::from)
+        .collect_vec();
+    if !to_download.is_empty() {
+        println!("\n{}\n", "Downloading Mod Files".bold());
+        let (_, failures) = download(tmp_dir.clone(), to_download, vec![]).await?;
+        ensure!(
+            failures.is_empty(),
+            "\nCould not download some files, the profile was not exported"
+        );
+    }
+
+    Ok((locked, tmp_dir))
+}
+
+/// Export `profile` as a Modrinth modpack to `output`
+///
+/// Files that Modrinth does not allow modpacks to download, such as those from CurseForge,
+/// are embedded in the modpack's overrides. The contents of `overrides` are also embedded if provided.
+pub async fn mrpack(
+    profile: &Profile,
+    settings: &mut ProfileSettings,
+    output: &Path,
+    pack_version: String,
+    loader_version: Option<String>,
+    overrides: Option<&Path>,
+) -> Result<()> {
+    let (game_version, mod_loader, loader_version) =
+        game_and_loader(profile, loader_version).await?;
+    // All files are downloaded to calculate their hashes
+    let (locked, tmp_dir) = resolve_and_download(profile, settings, |_| true).await?;
+
+    let mut zip = ZipWriter::new(File::create(output)?);
+    let options = SimpleFileOptions::default();
+    let mut files = Vec::new();
+    for file in locked {
+        let path = tmp_dir.join(&file.filename);
+        if file
+            .url
+            .host_str()
+            .is_some_and(|host| MRPACK_ALLOWED_HOSTS.contains(&host))
+        {
+            let (sha1, sha512) = hash_file(&path)?;
+            files.push(MRIndexFile {
+                path: format!("mods/{}", file.filename),
+                hashes: BTreeMap::from([("sha1", sha1), ("sha512", sha512)]),
+                downloads: vec![file.url.to_string()],
+                file_size: file.size,
+            });
+        } else {
+            zip.start_file(format!("overrides/mods/{}", file.filename), options)?;
+            zip.write_all(&read(path)?)?;
+        }
+    }
+    if let Some(overrides) = overrides {
+        add_directory(&mut zip, overrides, "overrides", options)?;
+    }
+
+    let index = MRIndex {
+        format_version: 1,
+        game: "minecraft",
+        version_id: pack_version,
+        name: profile.name.clone(),
+        files,
+        dependencies: BTreeMap::from([
+            ("minecraft", game_version),
+            (
+                match mod_loader {
+                    ModLoader::Fabric => "fabric-loader",
+                    ModLoader::Quilt => "quilt-loader",
+                    ModLoader::Forge => "forge",
+                    ModLoader::NeoForge => "neoforge",
+                },
+                loader_version,
+            ),
+        ]),
+    };
+    zip.start_file("modrinth.index.json", options)?;
+    zip.write_all(serde_json::to_string_pretty(&index)?.as_bytes())?;
+    finish(zip, &tmp_dir, profile, output)
+}
+
+/// Export `profile` as a CurseForge modpack to `output`
+///
+/// Files that are not from CurseForge are embedded in the modpack's overrides.
+/// The contents of `overrides` are also embedded if provided.
+pub async fn curseforge(
+    profile: &Profile,
+    settings: &mut ProfileSettings,
+    output: &Path,
+    pack_version: String,
+    loader_version: Option<String>,
+    overrides: Option<&Path>,
+) -> Result<()> {
+    let (game_version, mod_loader, loader_version) =
+        game_and_loader(profile, loader_version).await?;
+    let (locked, tmp_dir) = resolve_and_download(profile, settings, |file| {
+        !matches!(file.project, ModIdentifier::CurseForgeProject(_))
+    })
+    .await?;
+
+    let mut zip = ZipWriter::new(File::create(output)?);
+    let options = SimpleFileOptions::default();
+    let mut files = Vec::new();
+    for file in locked {
+        if let ModIdentifier::CurseForgeProject(project_id) = file.project {
+            files.push(CFManifestFile {
+                project_id,
+                file_id: file
+                    .file_id
+                    .parse()
+                    .with_context(|| format!("{} has an invalid CurseForge file ID", file.name))?,
+                required: true,
+            });
+        } else {
+            zip.start_file(format!("overrides/mods/{}", file.filename), options)?;
+            zip.write_all(&read(tmp_dir.join(&file.filename))?)?;
+        }
+    }
+    if let Some(overrides) = overrides {
+        add_directory(&mut zip, overrides, "overrides", options)?;
+    }
+
+    let manifest = CFManifest {
+        minecraft: CFMinecraft {
+            version: game_version,
+            mod_loaders: vec![CFModLoader {
+                id: format!(
+                    "{}-{loader_version}",
+                    match mod_loader {
+                        ModLoader::Fabric => "fabric",
+                        ModLoader::Quilt => "quilt",
+                        ModLoader::Forge => "forge",
+                        ModLoader::NeoForge => "neoforge",
+                    }
+                ),
+                primary: true,
+            }],
+        },
+        manifest_type: "minecraftModpack",
+        manifest_version: 1,
+        name: profile.name.clone(),
+        version: pack_version,
+        author: String::new(),
+        files,
+        overrides: "overrides",
+    };
+    zip.start_file("manifest.json", options)?;
+    zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+    finish(zip, &tmp_dir, profile, output)
+}
+
+/// Finish writing `zip` and delete the temporary directory the mods were downloaded to
+fn finish(zip: ZipWriter<File>, tmp_dir: &Path, profile: &Profile, output: &Path) -> Result<()> {
+    zip.finish()?;
+    if tmp_dir.exists() {
+        remove_dir_all(tmp_dir)?;
+    }
+
+    println!(
+        "\n{} Exported {} to {}",
+        &*TICK,
+        profile.name.bold(),
+        output.display().to_string().blue().underline()
+    );
+    Ok(())
+}
@@ -1,6 +1,7 @@
 mod configure;
 mod create;
 mod delete;
+pub mod export;
 mod info;
 mod switch;
 pub use configure::configure;
@@ -9,7 +10,7 @@ pub use delete::delete;
 pub use info::info;
 pub use switch::switch;
 
-use crate::file_picker::pick_folder;
+use crate::{file_picker::pick_folder, prompt};
 use anyhow::{ensure, Context as _, Result};
 use colored::Colorize as _;
 use ferinth::Ferinth;
@@ -106,9 +107,10 @@ pub async fn check_output_directory(output_dir: &PathBuf) -> Result<()> {
         println!(
             "There are files in your output directory, these will be deleted when you upgrade."
         );
-        if Confirm::new("Would like to create a backup?")
-            .prompt()
-            .unwrap_or_default()
+        if prompt::can_prompt()
+            && Confirm::new("Would like to create a backup?")
+                .prompt()
+                .unwrap_or_default()
         {
             let backup_dir = pick_folder(
                 &*HOME,
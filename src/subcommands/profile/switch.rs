@@ -1,3 +1,4 @@
+use crate::prompt::{can_prompt, required};
 use anyhow::{anyhow, Result};
 use colored::Colorize as _;
 use inquire::Select;
@@ -21,6 +22,8 @@ pub fn switch(config: &mut Config, profile_name: Option<String>) -> Result<()> {
             }
             None => Err(anyhow!("The profile provided does not exist")),
         }
+    } else if !can_prompt() {
+        Err(required("the profile to switch to", "its name"))
     } else {
         let profile_info = config
             .profiles
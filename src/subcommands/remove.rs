@@ -1,3 +1,7 @@
+use crate::{
+    prompt::{can_prompt, required},
+    settings::{pin_key, ProfileSettings},
+};
 use anyhow::{bail, Result};
 use colored::Colorize as _;
 use inquire::MultiSelect;
@@ -8,9 +12,17 @@ use libium::{
 
 /// If `to_remove` is empty, display a list of projects in the profile to select from and remove selected ones
 ///
-/// Else, search the given strings with the projects' name and IDs and remove them
-pub fn remove(profile: &mut Profile, to_remove: Vec<String>) -> Result<()> {
+/// Else, search the given strings with the projects' name and IDs and remove them.
+/// The pins of the removed mods are removed from `settings`.
+pub fn remove(
+    profile: &mut Profile,
+    settings: &mut ProfileSettings,
+    to_remove: Vec<String>,
+) -> Result<()> {
     let mut indices_to_remove = if to_remove.is_empty() {
+        if !can_prompt() {
+            return Err(required("the mods to remove", "their names or IDs"));
+        }
         let mod_info = profile
             .mods
             .iter()
@@ -19,13 +31,15 @@ pub fn remove(profile: &mut Profile, to_remove: Vec<String>) -> Result<()> {
                     "{:11}  {}",
                     match &mod_.identifier {
                         ModIdentifier::CurseForgeProject(id) => format!("CF {:8}", id.to_string()),
-                        ModIdentifier::ModrinthProject(id) => format!("MR {id:8}"),
+                        ModIdentifier::ModrinthProject(id)
+                        | ModIdentifier::PinnedModrinthProject(id, _) => format!("MR {id:8}"),
                         ModIdentifier::GitHubRepository(..) => "GH".to_string(),
                         _ => todo!(),
                     },
                     match &mod_.identifier {
-                        ModIdentifier::ModrinthProject(_) | ModIdentifier::CurseForgeProject(_) =>
-                            mod_.name.clone(),
+                        ModIdentifier::ModrinthProject(_)
+                        | ModIdentifier::PinnedModrinthProject(..)
+                        | ModIdentifier::CurseForgeProject(_) => mod_.name.clone(),
                         ModIdentifier::GitHubRepository(owner, repo) => format!("{owner}/{repo}"),
                         _ => todo!(),
                     },
@@ -45,7 +59,8 @@ pub fn remove(profile: &mut Profile, to_remove: Vec<String>) -> Result<()> {
                 mod_.name.eq_ignore_ascii_case(&to_remove)
                     || match &mod_.identifier {
                         ModIdentifier::CurseForgeProject(id) => id.to_string() == to_remove,
-                        ModIdentifier::ModrinthProject(id) => id == &to_remove,
+                        ModIdentifier::ModrinthProject(id)
+                        | ModIdentifier::PinnedModrinthProject(id, _) => id == &to_remove,
                         ModIdentifier::GitHubRepository(owner, name) => {
                             format!("{owner}/{name}").eq_ignore_ascii_case(&to_remove)
                         }
@@ -66,7 +81,11 @@ pub fn remove(profile: &mut Profile, to_remove: Vec<String>) -> Result<()> {
 
     let mut removed = Vec::new();
     for index in indices_to_remove {
-        removed.push(profile.mods.swap_remove(index).name);
+        let mod_ = profile.mods.swap_remove(index);
+        if let Some(key) = pin_key(&mod_.identifier) {
+            settings.pins.remove(&key);
+        }
+        removed.push(mod_.name);
     }
 
     if !removed.is_empty() {
@@ -0,0 +1,64 @@
+use crate::{generations, TICK};
+use anyhow::{ensure, Context as _, Result};
+use colored::Colorize as _;
+use std::{fs::read_dir, path::Path};
+
+/// Restore the files in `directory` from `generation`, or the latest generation if not provided
+///
+/// The latest generation contains the files from before the last upgrade or rollback.
+pub fn rollback(directory: &Path, generation: Option<usize>) -> Result<()> {
+    let generations = generations::list(directory)?;
+    let generation = if let Some(generation) = generation {
+        ensure!(
+            generations.contains(&generation),
+            "Generation {generation} does not exist, run `ferium rollback --list` to see the available generations"
+        );
+        generation
+    } else {
+        *generations
+            .last()
+            .context("There are no generations to roll back to, they are created when upgrading")?
+    };
+
+    let saved = generations::restore(directory, generation)?;
+    generations::prune(directory, generations::DEFAULT_RETENTION)?;
+    println!(
+        "{} Restored generation {}, the replaced files were saved as generation {}",
+        &*TICK,
+        generation.to_string().bold(),
+        saved.to_string().bold(),
+    );
+    Ok(())
+}
+
+/// List the generations in `directory` along with the number of files in them
+pub fn list(directory: &Path) -> Result<()> {
+    let generations = generations::list(directory)?;
+    ensure!(
+        !generations.is_empty(),
+        "There are no generations, they are created when upgrading"
+    );
+    for generation in generations {
+        println!(
+            "{:>4}  {}",
+            generation.to_string().bold(),
+            format!(
+                "({} files)",
+                read_dir(generations::path(directory, generation))?.count()
+            )
+            .yellow(),
+        );
+    }
+    Ok(())
+}
+
+/// Delete all but the latest `retention` generations in `directory`
+pub fn prune(directory: &Path, retention: usize) -> Result<()> {
+    let pruned = generations::prune(directory, retention)?;
+    println!(
+        "{} Deleted {} generation(s)",
+        &*TICK,
+        pruned.len().to_string().bold()
+    );
+    Ok(())
+}
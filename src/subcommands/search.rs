@@ -0,0 +1,193 @@
+use crate::{cli::Platform, prompt::can_prompt, settings::mod_key, TICK};
+use anyhow::Result;
+use colored::Colorize as _;
+use ferinth::structures::{
+    project::ProjectType,
+    search::{Facet, Sort},
+};
+use furse::structures::{common_structs::ModLoaderType, mod_structs::Mod as CFMod};
+use inquire::MultiSelect;
+use libium::{
+    config::{
+        filters::ProfileParameters as _,
+        structs::{ModIdentifier, ModLoader, Profile},
+    },
+    iter_ext::IterExt as _,
+    CURSEFORGE_API, MODRINTH_API,
+};
+
+/// CurseForge's ID for Minecraft
+const CF_MINECRAFT_ID: i32 = 432;
+/// CurseForge's ID for the mods class of Minecraft projects
+const CF_MODS_CLASS_ID: i32 = 6;
+
+/// A project found by searching one of the platforms
+struct SearchResult {
+    identifier: ModIdentifier,
+    name: String,
+    summary: String,
+    downloads: usize,
+}
+
+impl std::fmt::Display for SearchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}  {:>6}  {}  {}",
+            match self.identifier {
+                ModIdentifier::CurseForgeProject(_) => "CF",
+                _ => "MR",
+            },
+            format_downloads(self.downloads),
+            self.name,
+            self.summary,
+        )
+    }
+}
+
+/// Format a download count compactly, e.g. `12.3M`
+#[expect(clippy::cast_precision_loss, reason = "Only used for displaying")]
+fn format_downloads(downloads: usize) -> String {
+    match downloads {
+        0..1_000 => downloads.to_string(),
+        1_000..1_000_000 => format!("{:.1}K", downloads as f64 / 1e3),
+        1_000_000..1_000_000_000 => format!("{:.1}M", downloads as f64 / 1e6),
+        _ => format!("{:.1}B", downloads as f64 / 1e9),
+    }
+}
+
+/// Whether the CurseForge mod loader `loader` is the same as `mod_loader`
+fn cf_loader_matches(loader: &ModLoaderType, mod_loader: &ModLoader) -> bool {
+    matches!(
+        (loader, mod_loader),
+        (ModLoaderType::Fabric, ModLoader::Fabric)
+            | (ModLoaderType::Quilt, ModLoader::Quilt)
+            | (ModLoaderType::Forge, ModLoader::Forge)
+            | (ModLoaderType::NeoForge, ModLoader::NeoForge)
+    )
+}
+
+/// Search Modrinth for mods matching `query` that are compatible with `game_versions` and `mod_loader`
+async fn search_modrinth(
+    query: &str,
+    game_versions: &[String],
+    mod_loader: Option<&ModLoader>,
+    limit: usize,
+) -> Result<Vec<SearchResult>> {
+    // Facets in the same inner list are OR-ed, and the lists are AND-ed together
+    let mut facets = vec![vec![Facet::ProjectType(ProjectType::Mod)]];
+    if !game_versions.is_empty() {
+        facets.push(
+            game_versions
+                .iter()
+                .map(|version| Facet::Versions(version.clone()))
+                .collect_vec(),
+        );
+    }
+    if let Some(mod_loader) = mod_loader {
+        facets.push(vec![Facet::Categories(
+            mod_loader.to_string().to_lowercase(),
+        )]);
+    }
+
+    Ok(MODRINTH_API
+        .search_paged(query, &Sort::Relevance, limit, 0, facets)
+        .await?
+        .hits
+        .into_iter()
+        .map(|hit| SearchResult {
+            identifier: ModIdentifier::ModrinthProject(hit.project_id),
+            name: hit.title,
+            summary: hit.description,
+            downloads: hit.downloads,
+        })
+        .collect_vec())
+}
+
+/// Search CurseForge for mods matching `query` that are compatible with `game_versions` and `mod_loader`
+///
+/// The mods are filtered using the latest files CurseForge lists for them.
+async fn search_curseforge(
+    query: &str,
+    game_versions: &[String],
+    mod_loader: Option<&ModLoader>,
+    limit: usize,
+) -> Result<Vec<SearchResult>> {
+    let compatible = |project: &CFMod| {
+        project.latest_files_indexes.iter().any(|file| {
+            (game_versions.is_empty() || game_versions.contains(&file.game_version))
+                && mod_loader.map_or(true, |mod_loader| {
+                    file.mod_loader
+                        .as_ref()
+                        .is_some_and(|loader| cf_loader_matches(loader, mod_loader))
+                })
+        })
+    };
+
+    Ok(CURSEFORGE_API
+        .search_mods(CF_MINECRAFT_ID, Some(CF_MODS_CLASS_ID), Some(query))
+        .await?
+        .into_iter()
+        .filter(compatible)
+        .take(limit)
+        .map(|project| SearchResult {
+            identifier: ModIdentifier::CurseForgeProject(project.id),
+            name: project.name,
+            summary: project.summary,
+            downloads: project.download_count,
+        })
+        .collect_vec())
+}
+
+/// Search for mods matching `query` that are compatible with `profile`, and let the user pick the ones to add
+///
+/// Both platforms are searched unless `platform` is provided, with up to `limit` results from each.
+/// Returns the identifiers of the picked mods.
+pub async fn search(
+    profile: &Profile,
+    query: &str,
+    platform: Option<Platform>,
+    limit: usize,
+) -> Result<Vec<ModIdentifier>> {
+    let game_versions = profile.filters.game_versions().cloned().unwrap_or_default();
+    let mod_loader = profile.filters.mod_loader();
+
+    eprint!("Searching... ");
+    let mut results = Vec::new();
+    if !matches!(platform, Some(Platform::Curseforge)) {
+        results.extend(search_modrinth(query, &game_versions, mod_loader, limit).await?);
+    }
+    if !matches!(platform, Some(Platform::Modrinth)) {
+        results.extend(search_curseforge(query, &game_versions, mod_loader, limit).await?);
+    }
+    eprintln!("{}", &*TICK);
+
+    if results.is_empty() {
+        println!(
+            "{}",
+            format!("No compatible mods found for {query:?}").yellow()
+        );
+        return Ok(vec![]);
+    }
+    results.sort_unstable_by_key(|result| std::cmp::Reverse(result.downloads));
+
+    // The results can't be picked from, so list them along with their identifiers instead
+    if !can_prompt() {
+        for result in &results {
+            println!("{result}  {}", mod_key(&result.identifier).dimmed());
+        }
+        println!(
+            "{}",
+            "Input is disabled, add mods using `ferium add <identifier>`".yellow()
+        );
+        return Ok(vec![]);
+    }
+
+    Ok(MultiSelect::new("Select mods to add", results)
+        .with_page_size(15)
+        .prompt_skippable()?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|result| result.identifier)
+        .collect_vec())
+}
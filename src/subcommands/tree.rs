@@ -0,0 +1,163 @@
+use super::upgrade::{get_platform_downloadables, DependencyGraph};
+use crate::{
+    retry::retry_request,
+    settings::{mod_key, ProfileSettings},
+};
+use anyhow::{bail, Result};
+use colored::Colorize as _;
+use libium::{
+    config::structs::{Mod, ModIdentifier, Profile},
+    iter_ext::IterExt as _,
+    upgrade::DownloadData,
+    CURSEFORGE_API, MODRINTH_API,
+};
+use std::collections::HashMap;
+
+/// Get the names of the dependencies in `graph`, keyed by their [`mod_key`]
+///
+/// Uses a maximum of 2 network requests.
+async fn dependency_names(graph: &DependencyGraph) -> Result<HashMap<String, String>> {
+    let mut names = HashMap::new();
+
+    let mut mr_ids = graph
+        .edges
+        .iter()
+        .filter_map(|(_, dep)| match dep {
+            ModIdentifier::ModrinthProject(id) | ModIdentifier::PinnedModrinthProject(id, _) => {
+                Some(id.as_str())
+            }
+            _ => None,
+        })
+        .collect_vec();
+    mr_ids.sort_unstable();
+    mr_ids.dedup();
+    if !mr_ids.is_empty() {
+        for project in retry_request(|| MODRINTH_API.get_multiple_projects(&mr_ids)).await? {
+            names.insert(project.id, project.title);
+        }
+    }
+
+    let mut cf_ids = graph
+        .edges
+        .iter()
+        .filter_map(|(_, dep)| match dep {
+            ModIdentifier::CurseForgeProject(id) => Some(*id),
+            _ => None,
+        })
+        .collect_vec();
+    cf_ids.sort_unstable();
+    cf_ids.dedup();
+    if !cf_ids.is_empty() {
+        for project in retry_request(|| CURSEFORGE_API.get_mods(cf_ids.clone())).await? {
+            names.insert(project.id.to_string(), project.name);
+        }
+    }
+
+    Ok(names)
+}
+
+/// The state used while printing the dependency tree of a profile
+struct Tree<'a> {
+    resolved: &'a [(Mod, DownloadData)],
+    graph: &'a DependencyGraph,
+    names: HashMap<String, String>,
+    /// The mods whose dependencies have already been printed
+    expanded: Vec<&'a ModIdentifier>,
+}
+
+impl<'a> Tree<'a> {
+    fn file(&self, identifier: &ModIdentifier) -> Option<&'a DownloadData> {
+        self.resolved
+            .iter()
+            .find(|(mod_, _)| &mod_.identifier == identifier)
+            .map(|(_, download_file)| download_file)
+    }
+
+    /// Print the dependencies of the mod with `parent`, recursively, with every line starting with `prefix`
+    fn print_dependencies(&mut self, parent: &ModIdentifier, prefix: &str) {
+        let graph = self.graph;
+        let mut dependencies = graph
+            .edges
+            .iter()
+            .filter(|(mod_, _)| mod_ == parent)
+            .map(|(_, dep)| dep)
+            .collect_vec();
+        dependencies.dedup();
+
+        for (i, dep) in dependencies.iter().enumerate() {
+            let last = i + 1 == dependencies.len();
+            let key = mod_key(dep);
+            let name = self.names.get(&key).unwrap_or(&key);
+            let file = self.file(dep);
+            let requested_by = graph.edges.iter().filter(|(_, d)| d == *dep).count();
+
+            let note = if graph.ignored.contains(dep) {
+                Some("ignored, a different version was requested first".yellow())
+            } else if file.is_none() {
+                Some("not installed".red())
+            } else if self.expanded.contains(dep) {
+                Some("deduplicated".dimmed())
+            } else if requested_by > 1 {
+                Some(format!("shared by {requested_by} mods").cyan())
+            } else {
+                None
+            };
+            println!(
+                "{prefix}{} {}  {}{}",
+                if last { "└──" } else { "├──" },
+                name,
+                file.map(DownloadData::filename)
+                    .unwrap_or_default()
+                    .dimmed(),
+                note.map(|note| format!("  ({note})")).unwrap_or_default()
+            );
+
+            if file.is_some() && !self.expanded.contains(dep) {
+                self.expanded.push(*dep);
+                self.print_dependencies(
+                    dep,
+                    &format!("{prefix}{}", if last { "    " } else { "│   " }),
+                );
+            }
+        }
+    }
+}
+
+/// Resolve the latest compatible files for `profile` and print each mod with its transitive dependencies
+///
+/// Dependencies that are requested by multiple mods are marked as shared,
+/// and their dependencies are only printed the first time they appear.
+pub async fn tree(profile: &Profile, settings: &mut ProfileSettings) -> Result<()> {
+    let (resolved, graph, failures) = get_platform_downloadables(profile, settings).await?;
+    let names = dependency_names(&graph).await?;
+
+    let mut mods = profile.mods.iter().collect_vec();
+    mods.sort_unstable_by_key(|mod_| mod_.name.to_lowercase());
+    let mut tree = Tree {
+        resolved: &resolved,
+        graph: &graph,
+        names,
+        // The configured mods are printed at the top level
+        expanded: profile
+            .mods
+            .iter()
+            .map(|mod_| &mod_.identifier)
+            .collect_vec(),
+    };
+
+    println!();
+    for mod_ in mods {
+        let file = tree.file(&mod_.identifier);
+        println!(
+            "{}  {}",
+            mod_.name.bold(),
+            file.map_or_else(|| "not installed".red(), |file| file.filename().dimmed())
+        );
+        tree.print_dependencies(&mod_.identifier, "");
+    }
+
+    if !failures.is_empty() {
+        bail!("\nCould not get the latest compatible version of some mods");
+    }
+    Ok(())
+}
@@ -1,37 +1,331 @@
 #![expect(clippy::expect_used, reason = "For mutex poisons")]
 
 use crate::{
-    download::{clean, download},
+    cache, changelog, conflicts, dedup,
+    download::{clean, download, plan_clean, print_downloads, Downloadable},
+    lockfile::{self, FileId, LockedFile},
+    prompt::can_prompt,
+    report::{AddedDependency, Failure, Report, ResolvedFile},
+    retry::{retry, retry_request},
+    settings::{mod_key, OptionalDependencies, ProfileSettings, Side},
     CROSS, DEFAULT_PARALLEL_NETWORK, PARALLEL_NETWORK, STYLE_NO, TICK,
 };
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, bail, ensure, Context as _, Result};
 use colored::Colorize as _;
+use ferinth::structures::{project::ProjectSupportRange, version::DependencyType};
+use furse::structures::file_structs::FileRelationType;
 use indicatif::ProgressBar;
+use inquire::MultiSelect;
 use libium::{
     config::{
         filters::ProfileParameters as _,
         structs::{Mod, ModIdentifier, ModLoader, Profile},
     },
-    upgrade::{mod_downloadable, DownloadData},
+    iter_ext::IterExt as _,
+    upgrade::{from_gh_asset, mod_downloadable, try_from_cf_file, DownloadData},
+    CURSEFORGE_API, GITHUB_API, MODRINTH_API,
 };
+use octocrab::models::repos::Asset;
 use std::{
+    collections::HashMap,
     fs::read_dir,
     mem::take,
+    path::Path,
     sync::{mpsc, Arc, Mutex},
     time::Duration,
 };
 use tokio::{sync::Semaphore, task::JoinSet};
 
-/// Get the latest compatible downloadable for the mods in `profile`
+/// An optional dependency declared by a resolved file
+#[derive(Clone)]
+struct OptionalDependency {
+    identifier: ModIdentifier,
+    name: String,
+}
+
+impl std::fmt::Display for OptionalDependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl From<OptionalDependency> for Mod {
+    fn from(dependency: OptionalDependency) -> Self {
+        Self {
+            name: dependency.name,
+            identifier: dependency.identifier,
+            filters: vec![],
+            override_filters: false,
+        }
+    }
+}
+
+/// What the file resolved for a mod declares about where and with what it is installed
+struct FileMetadata {
+    /// Whether the file should be installed on the profile's side
+    allowed: bool,
+    /// The optional dependencies declared by the file, along with their names
+    optional: Vec<OptionalDependency>,
+}
+
+/// Get the [`FileMetadata`] of `download_file`, resolved for the mod with `identifier`
+///
+/// Modrinth projects declare their server side support, which is looked up in `server_sides` before it is fetched,
+/// while CurseForge files list it among their game versions.
+/// A CurseForge file is fetched once for both its side support and its optional dependencies.
+/// The optional dependencies are only fetched if `with_optional` is true, GitHub releases don't have any.
+async fn file_metadata(
+    side: Side,
+    identifier: &ModIdentifier,
+    download_file: &DownloadData,
+    with_optional: bool,
+    server_sides: &HashMap<String, ProjectSupportRange>,
+) -> Result<FileMetadata> {
+    let mut metadata = FileMetadata {
+        allowed: true,
+        optional: vec![],
+    };
+    match FileId::from_url(&download_file.download_url) {
+        Some(FileId::Modrinth(version_id)) => {
+            if side == Side::Server {
+                if let ModIdentifier::ModrinthProject(id)
+                | ModIdentifier::PinnedModrinthProject(id, _) = identifier
+                {
+                    metadata.allowed = match server_sides.get(id) {
+                        Some(server_side) => side.allows_modrinth(server_side),
+                        None => {
+                            side.allows_modrinth(&MODRINTH_API.get_project(id).await?.server_side)
+                        }
+                    };
+                }
+            }
+            if !with_optional || !metadata.allowed {
+                return Ok(metadata);
+            }
+            let ids = MODRINTH_API
+                .get_version(&version_id)
+                .await?
+                .dependencies
+                .into_iter()
+                .filter(|dep| matches!(dep.dependency_type, DependencyType::Optional))
+                .filter_map(|dep| dep.project_id)
+                .collect_vec();
+            if !ids.is_empty() {
+                metadata.optional = MODRINTH_API
+                    .get_multiple_projects(&ids.iter().map(String::as_str).collect_vec())
+                    .await?
+                    .into_iter()
+                    .map(|project| OptionalDependency {
+                        identifier: ModIdentifier::ModrinthProject(project.id),
+                        name: project.title,
+                    })
+                    .collect_vec();
+            }
+        }
+        Some(FileId::CurseForge(file_id)) => {
+            if side == Side::Client && !with_optional {
+                return Ok(metadata);
+            }
+            let file = CURSEFORGE_API
+                .get_files(vec![file_id])
+                .await?
+                .into_iter()
+                .next()
+                .with_context(|| format!("CurseForge file {file_id} does not exist"))?;
+            metadata.allowed = side.allows_curseforge(&file.sortable_game_versions);
+            if !with_optional || !metadata.allowed {
+                return Ok(metadata);
+            }
+            let ids = file
+                .dependencies
+                .into_iter()
+                .filter(|dep| matches!(dep.relation_type, FileRelationType::OptionalDependency))
+                .map(|dep| dep.mod_id)
+                .collect_vec();
+            if !ids.is_empty() {
+                metadata.optional = CURSEFORGE_API
+                    .get_mods(ids)
+                    .await?
+                    .into_iter()
+                    .map(|project| OptionalDependency {
+                        identifier: ModIdentifier::CurseForgeProject(project.id),
+                        name: project.name,
+                    })
+                    .collect_vec();
+            }
+        }
+        _ => (),
+    }
+    Ok(metadata)
+}
+
+/// Whether `token` of a filename names a Minecraft version, e.g. `1.21.1` or `mc1.20`
+fn is_game_version(token: &str) -> bool {
+    let token = token.trim_start_matches("mc");
+    token.starts_with("1.")
+        && token
+            .split('.')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Pick the JAR file among the `assets` of a GitHub release that is compatible with `game_versions` and `mod_loader`
 ///
+/// Assets whose filename mentions only other game versions or mod loaders are skipped,
+/// and assets mentioning `mod_loader` are preferred.
+pub fn github_asset(
+    assets: Vec<Asset>,
+    game_versions: &[String],
+    mod_loader: Option<&ModLoader>,
+) -> Option<Asset> {
+    const LOADERS: [&str; 4] = ["fabric", "quilt", "forge", "neoforge"];
+    let loader = mod_loader.map(|loader| loader.to_string().to_lowercase());
+    let mut loaders = loader.iter().map(String::as_str).collect_vec();
+    // Quilt can load Fabric mods
+    if mod_loader == Some(&ModLoader::Quilt) {
+        loaders.push("fabric");
+    }
+    let mut jars = assets
+        .into_iter()
+        .filter(|asset| {
+            let name = asset.name.to_lowercase();
+            if !name.ends_with(".jar")
+                || name.ends_with("-sources.jar")
+                || name.ends_with("-dev.jar")
+            {
+                return false;
+            }
+            let tokens = name
+                .trim_end_matches(".jar")
+                .split(|c: char| !c.is_ascii_alphanumeric() && c != '.')
+                .collect_vec();
+            let versions = tokens
+                .iter()
+                .copied()
+                .filter(|token| is_game_version(token))
+                .map(|token| token.trim_start_matches("mc"))
+                .collect_vec();
+            let mentioned_loaders = tokens
+                .iter()
+                .copied()
+                .filter(|token| LOADERS.contains(token))
+                .collect_vec();
+            (game_versions.is_empty()
+                || versions.is_empty()
+                || versions
+                    .iter()
+                    .any(|version| game_versions.iter().any(|v| v == version)))
+                && (loaders.is_empty()
+                    || mentioned_loaders.is_empty()
+                    || mentioned_loaders
+                        .iter()
+                        .any(|loader| loaders.contains(loader)))
+        })
+        .collect_vec();
+    let index = jars
+        .iter()
+        .position(|asset| {
+            loaders
+                .first()
+                .is_some_and(|loader| asset.name.to_lowercase().contains(loader))
+        })
+        .or((!jars.is_empty()).then_some(0))?;
+    Some(jars.swap_remove(index))
+}
+
+/// Get the file of the mod with `identifier` that is pinned to `pin`, a CurseForge file ID or GitHub release tag
+///
+/// The JAR file of a GitHub release is picked using [`github_asset`].
+async fn fetch_pinned(
+    identifier: &ModIdentifier,
+    pin: &str,
+    game_versions: &[String],
+    mod_loader: Option<&ModLoader>,
+) -> Result<DownloadData> {
+    match identifier {
+        ModIdentifier::CurseForgeProject(id) => {
+            let file = CURSEFORGE_API.get_mod_file(*id, pin.parse()?).await?;
+            Ok(try_from_cf_file(file)?.1)
+        }
+        ModIdentifier::GitHubRepository(owner, repo) => {
+            let assets = GITHUB_API
+                .repos(owner, repo)
+                .releases()
+                .get_by_tag(pin)
+                .await?
+                .assets;
+            let asset = github_asset(assets, game_versions, mod_loader).with_context(|| {
+                format!("The release {pin} does not have any compatible JAR files")
+            })?;
+            Ok(from_gh_asset(asset))
+        }
+        _ => bail!("Only CurseForge and GitHub mods can be pinned this way"),
+    }
+}
+
+/// The dependency relations discovered while resolving a profile
+#[derive(Default)]
+pub struct DependencyGraph {
+    /// The mods that requested each dependency, as `(mod, dependency)` pairs
+    pub edges: Vec<(ModIdentifier, ModIdentifier)>,
+    /// The dependencies that were ignored because a different version of them was requested first
+    pub ignored: Vec<ModIdentifier>,
+}
+
+/// Get the latest compatible downloadable for the mods in `profile`, along with the mod it was resolved for
+///
+/// Mods that are not supported on the profile's side are skipped along with their dependencies,
+/// and mods pinned in `settings` are resolved to the file they are pinned to.
+/// Optional dependencies are installed according to the profile's policy,
+/// the user's picks are saved in `settings` if they are asked which ones to install.
+/// The user is asked once the other mods have been resolved, and the picked ones are resolved after that.
+/// The dependency relations between the mods are returned too.
 /// If an error occurs with a resolving task, instead of failing immediately,
-/// resolution will continue and the error return flag is set to true.
-pub async fn get_platform_downloadables(profile: &Profile) -> Result<(Vec<DownloadData>, bool)> {
+/// resolution will continue and the mod is returned as a failure.
+pub async fn get_platform_downloadables(
+    profile: &Profile,
+    settings: &mut ProfileSettings,
+) -> Result<(Vec<(Mod, DownloadData)>, DependencyGraph, Vec<Failure>)> {
+    let side = settings.side;
+    let optional_policy = settings.optional_dependencies;
     let to_download = Arc::new(Mutex::new(Vec::new()));
+    let edges = Arc::new(Mutex::new(Vec::new()));
+    let mut ignored = Vec::new();
     let progress_bar = Arc::new(Mutex::new(ProgressBar::new(0).with_style(STYLE_NO.clone())));
     let mut tasks = JoinSet::new();
     let mut done_mods = Vec::new();
     let (mod_sender, mod_rcvr) = mpsc::channel();
+    // Tasks send the optional dependencies that have to be asked about here,
+    // so that the user is prompted from one place
+    let (ask_sender, ask_rcvr) = mpsc::channel::<(Mod, Vec<OptionalDependency>)>();
+
+    // The server side support of the profile's Modrinth projects is fetched at once,
+    // so that only the dependencies' has to be fetched separately
+    let server_sides = Arc::new(if side == Side::Server {
+        let ids = profile
+            .mods
+            .iter()
+            .filter_map(|mod_| match &mod_.identifier {
+                ModIdentifier::ModrinthProject(id)
+                | ModIdentifier::PinnedModrinthProject(id, _) => Some(id.as_str()),
+                _ => None,
+            })
+            .collect_vec();
+        if ids.is_empty() {
+            HashMap::new()
+        } else {
+            retry_request(|| MODRINTH_API.get_multiple_projects(&ids))
+                .await
+                .map(|projects| {
+                    projects
+                        .into_iter()
+                        .map(|project| (project.id, project.server_side))
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+    } else {
+        HashMap::new()
+    });
 
     // Wrap it again in an Arc so that I can count the references to it,
     // because I cannot drop the main thread's sender due to the recursion
@@ -57,17 +351,16 @@ pub async fn get_platform_downloadables(profile: &Profile) -> Result<(Vec<Downlo
         mod_sender.send(mod_)?;
     }
 
-    let mut initial = true;
-
-    // A race condition exists where if the last task drops its sender before this thread receives the message,
-    // that particular message will get ignored. I used the ostrich algorithm to solve this.
-
-    // `initial` accounts for the edge case where at first,
-    // no tasks have been spawned yet but there are messages in the channel
-    while Arc::strong_count(&mod_sender) > 1 || initial {
+    // The optional dependencies to ask about once the mods that are being resolved are done
+    let mut asks = Vec::new();
+    loop {
+        // Count the running tasks before checking the channels,
+        // so that the messages sent by the last task before it finished are still received
+        let running = Arc::strong_count(&mod_sender) > 1;
+        while let Ok(ask) = ask_rcvr.try_recv() {
+            asks.push(ask);
+        }
         if let Ok(mod_) = mod_rcvr.try_recv() {
-            initial = false;
-
             if done_mods.contains(&mod_.identifier) {
                 continue;
             }
@@ -100,6 +393,7 @@ pub async fn get_platform_downloadables(profile: &Profile) -> Result<(Vec<Downlo
                             },
                             file_id,
                     ));
+                    ignored.push(mod_.identifier.clone());
                     continue;
                 }
             }
@@ -108,19 +402,115 @@ pub async fn get_platform_downloadables(profile: &Profile) -> Result<(Vec<Downlo
             progress_bar.lock().expect("Mutex poisoned").inc_length(1);
 
             let filters = profile.filters.clone();
+            let pin = settings.pin(&mod_.identifier).cloned();
+            let choices = settings
+                .optional_choices
+                .get(&mod_key(&mod_.identifier))
+                .cloned()
+                .unwrap_or_default();
             let dep_sender = Arc::clone(&mod_sender);
+            let ask_sender = ask_sender.clone();
             let semaphore = Arc::clone(&semaphore);
             let to_download = Arc::clone(&to_download);
+            let edges = Arc::clone(&edges);
+            let server_sides = Arc::clone(&server_sides);
             let progress_bar = Arc::clone(&progress_bar);
 
             tasks.spawn(async move {
                 let _permit = semaphore.acquire_owned().await?;
+                let on_retry = |err: &anyhow::Error, delay: Duration| {
+                    progress_bar
+                        .lock()
+                        .expect("Mutex poisoned")
+                        .println(format!(
+                            "{} {err}, retrying {} in {:.1}s",
+                            "Warning:".bold().yellow(),
+                            mod_.name,
+                            delay.as_secs_f64()
+                        ));
+                };
 
-                let result = mod_.fetch_download_file(filters).await;
+                let result = retry(
+                    || async {
+                        match &pin {
+                            Some(pin) => {
+                                fetch_pinned(
+                                    &mod_.identifier,
+                                    pin,
+                                    filters.game_versions().map_or(&[][..], Vec::as_slice),
+                                    filters.mod_loader(),
+                                )
+                                .await
+                            }
+                            None => mod_
+                                .fetch_download_file(filters.clone())
+                                .await
+                                .map_err(anyhow::Error::from),
+                        }
+                    },
+                    &on_retry,
+                )
+                .await;
 
                 progress_bar.lock().expect("Mutex poisoned").inc(1);
                 match result {
                     Ok(mut download_file) => {
+                        let optional = match retry(
+                            || {
+                                file_metadata(
+                                    side,
+                                    &mod_.identifier,
+                                    &download_file,
+                                    optional_policy != OptionalDependencies::RequiredOnly,
+                                    &server_sides,
+                                )
+                            },
+                            &on_retry,
+                        )
+                        .await
+                        {
+                            Ok(FileMetadata {
+                                allowed: true,
+                                optional,
+                            }) => optional,
+                            Ok(FileMetadata { allowed: false, .. }) => {
+                                progress_bar
+                                    .lock()
+                                    .expect("Mutex poisoned")
+                                    .println(format!(
+                                        "{} {:pad_len$}  {}",
+                                        "-".yellow(),
+                                        mod_.name,
+                                        format!("Skipped, not supported on {side}s").dimmed()
+                                    ));
+                                return Ok(None);
+                            }
+                            // Files are installed on clients even if their optional dependencies are unknown
+                            Err(err) if side == Side::Client => {
+                                progress_bar
+                                    .lock()
+                                    .expect("Mutex poisoned")
+                                    .println(format!(
+                                        "{} Could not get the optional dependencies of {}: {err}",
+                                        "Warning:".bold().yellow(),
+                                        mod_.name,
+                                    ));
+                                vec![]
+                            }
+                            Err(err) => {
+                                progress_bar
+                                    .lock()
+                                    .expect("Mutex poisoned")
+                                    .println(format!(
+                                        "{}",
+                                        format!("{CROSS} {:pad_len$}  {err}", mod_.name).red()
+                                    ));
+                                return Ok(Some(Failure {
+                                    name: mod_.name,
+                                    error: err.to_string(),
+                                }));
+                            }
+                        };
                         progress_bar
                             .lock()
                             .expect("Mutex poisoned")
@@ -131,6 +521,10 @@ pub async fn get_platform_downloadables(profile: &Profile) -> Result<(Vec<Downlo
                                 download_file.filename().dimmed()
                             ));
                         for dep in take(&mut download_file.dependencies) {
+                            edges
+                                .lock()
+                                .expect("Mutex poisoned")
+                                .push((mod_.identifier.clone(), dep.clone()));
                             dep_sender.send(Mod {
                                 name: format!(
                                     "Dependency: {}",
@@ -147,23 +541,43 @@ pub async fn get_platform_downloadables(profile: &Profile) -> Result<(Vec<Downlo
                                 override_filters: false,
                             })?;
                         }
+                        let mut undecided = Vec::new();
+                        for dep in optional {
+                            let choice = choices.get(&mod_key(&dep.identifier));
+                            if optional_policy == OptionalDependencies::All || choice == Some(&true)
+                            {
+                                edges
+                                    .lock()
+                                    .expect("Mutex poisoned")
+                                    .push((mod_.identifier.clone(), dep.identifier.clone()));
+                                dep_sender.send(dep.into())?;
+                            } else if choice.is_none() {
+                                undecided.push(dep);
+                            }
+                        }
+                        // Undecided optional dependencies aren't installed if they can't be asked about,
+                        // and are asked about the next time prompts are enabled
+                        if !undecided.is_empty() && can_prompt() {
+                            ask_sender.send((mod_.clone(), undecided))?;
+                        }
                         to_download
                             .lock()
                             .expect("Mutex poisoned")
-                            .push(download_file);
-                        Ok(true)
+                            .push((mod_, download_file));
+                        Ok(None)
                     }
                     Err(err) => {
-                        if let mod_downloadable::Error::ModrinthError(
+                        if let Some(mod_downloadable::Error::ModrinthError(
                             ferinth::Error::RateLimitExceeded(_),
-                        ) = err
+                        )) = err.downcast_ref()
                         {
-                            // Immediately fail if the rate limit has been exceeded
+                            // Fail the whole resolution if the rate limit is still exceeded after retrying,
+                            // as the rest of the mods will not resolve either
                             progress_bar
                                 .lock()
                                 .expect("Mutex poisoned")
                                 .finish_and_clear();
-                            bail!(err);
+                            return Err(err);
                         }
                         progress_bar
                             .lock()
@@ -172,18 +586,63 @@ pub async fn get_platform_downloadables(profile: &Profile) -> Result<(Vec<Downlo
                                 "{}",
                                 format!("{CROSS} {:pad_len$}  {err}", mod_.name).red()
                             ));
-                        Ok(false)
+                        Ok(Some(Failure {
+                            name: mod_.name,
+                            error: err.to_string(),
+                        }))
                     }
                 }
             });
+        } else if running {
+            tokio::task::yield_now().await;
+        } else if asks.is_empty() {
+            break;
+        } else {
+            // Ask about the optional dependencies once nothing else is being resolved,
+            // so that the prompts don't block the tasks. The picked ones are resolved afterwards.
+            for (mod_, optional) in take(&mut asks) {
+                let picked = progress_bar
+                    .lock()
+                    .expect("Mutex poisoned")
+                    .suspend(|| {
+                        MultiSelect::new(
+                            &format!(
+                                "Select the optional dependencies of {} to install",
+                                mod_.name
+                            ),
+                            optional.clone(),
+                        )
+                        .prompt_skippable()
+                    })?
+                    .unwrap_or_default();
+                let choices = settings
+                    .optional_choices
+                    .entry(mod_key(&mod_.identifier))
+                    .or_default();
+                for dep in optional {
+                    choices.insert(
+                        mod_key(&dep.identifier),
+                        picked.iter().any(|p| p.identifier == dep.identifier),
+                    );
+                }
+                for dep in picked {
+                    edges
+                        .lock()
+                        .expect("Mutex poisoned")
+                        .push((mod_.identifier.clone(), dep.identifier.clone()));
+                    mod_sender.send(dep.into())?;
+                }
+            }
         }
     }
 
-    let error = tasks
-        .join_all()
-        .await
-        .iter()
-        .any(|r| matches!(r, Ok(false)));
+    let mut failures = Vec::new();
+    for res in tasks.join_all().await {
+        // Errors that aren't specific to a mod fail the whole resolution
+        if let Some(failure) = res? {
+            failures.push(failure);
+        }
+    }
 
     Arc::try_unwrap(progress_bar)
         .map_err(|_| anyhow!("Failed to run threads to completion"))?
@@ -193,12 +652,227 @@ pub async fn get_platform_downloadables(profile: &Profile) -> Result<(Vec<Downlo
         Arc::try_unwrap(to_download)
             .map_err(|_| anyhow!("Failed to run threads to completion"))?
             .into_inner()?,
-        error,
+        DependencyGraph {
+            edges: Arc::try_unwrap(edges)
+                .map_err(|_| anyhow!("Failed to run threads to completion"))?
+                .into_inner()?,
+            ignored,
+        },
+        failures,
     ))
 }
 
-pub async fn upgrade(profile: &Profile) -> Result<()> {
-    let (mut to_download, error) = get_platform_downloadables(profile).await?;
+/// The files resolved for a profile, along with how they were resolved
+pub struct Resolution {
+    pub locked: Vec<LockedFile>,
+    pub graph: DependencyGraph,
+    /// The mods that could not be resolved
+    pub failures: Vec<Failure>,
+}
+
+impl Resolution {
+    /// Whether some mods could not be resolved
+    pub fn error(&self) -> bool {
+        !self.failures.is_empty()
+    }
+
+    /// Get the resolved mods that other mods depend on, along with the names of the mods depending on them
+    pub fn dependencies_added(&self, profile: &Profile) -> Vec<AddedDependency> {
+        let name = |identifier: &ModIdentifier| {
+            self.locked
+                .iter()
+                .find(|file| &file.project == identifier)
+                .map(|file| file.name.clone())
+        };
+        let mut added = Vec::<AddedDependency>::new();
+        for (mod_, dep) in &self.graph.edges {
+            // Only dependencies that were resolved and are not configured in the profile were added
+            let Some(dep_name) = name(dep) else {
+                continue;
+            };
+            if profile.mods.iter().any(|m| &m.identifier == dep) {
+                continue;
+            }
+            let required_by = name(mod_).unwrap_or_else(|| mod_key(mod_));
+            if let Some(added) = added.iter_mut().find(|a| a.identifier == mod_key(dep)) {
+                if !added.required_by.contains(&required_by) {
+                    added.required_by.push(required_by);
+                }
+            } else {
+                added.push(AddedDependency {
+                    name: dep_name,
+                    platform: lockfile::platform(dep),
+                    identifier: mod_key(dep),
+                    required_by: vec![required_by],
+                });
+            }
+        }
+        added
+    }
+}
+
+/// Resolve the latest compatible files for `profile` along with their lock entries
+///
+/// Mods that were resolved from multiple platforms are only included once.
+/// Fails if any of the resolved mods are declared incompatible with each other.
+pub async fn resolve(profile: &Profile, settings: &mut ProfileSettings) -> Result<Resolution> {
+    let (resolved, graph, failures) = get_platform_downloadables(profile, settings).await?;
+    let locked = dedup::deduplicate(profile, lockfile::lock(resolved).await?).await?;
+    conflicts::check(&locked).await?;
+    Ok(Resolution {
+        locked,
+        graph,
+        failures,
+    })
+}
+
+/// Resolve the latest compatible files for `profile` and record them in the lockfile at `lockfile_path`
+///
+/// The lockfile is not updated if some mods could not be resolved.
+pub async fn lock(
+    profile: &Profile,
+    settings: &mut ProfileSettings,
+    lockfile_path: &Path,
+) -> Result<Resolution> {
+    let resolution = resolve(profile, settings).await?;
+    if !resolution.error() {
+        let mut lockfile = lockfile::read(lockfile_path)?;
+        lockfile
+            .profiles
+            .insert(profile.name.clone(), resolution.locked.clone());
+        lockfile::write_file(lockfile_path, &lockfile)?;
+    }
+    Ok(resolution)
+}
+
+/// Download and install the mods in `profile`
+///
+/// If `locked` is true, the files recorded in the lockfile are installed without resolving the latest versions.
+/// If `offline` is true, the locked files are installed from the output directory and the download cache only,
+/// failing if any of them have never been downloaded.
+/// If `dry_run` is true, the changes that would be made are printed and nothing on disk is changed.
+/// If `show_changelog` is true, the changelogs of the mods being updated are shown in a pager,
+/// or written to `changelog_file` if provided.
+/// If `report_file` is provided, a [`Report`] of what was resolved and changed is written to it as JSON,
+/// even if some files could not be downloaded.
+#[expect(
+    clippy::too_many_arguments,
+    reason = "Most of them are options from the CLI"
+)]
+pub async fn upgrade(
+    profile: &Profile,
+    settings: &mut ProfileSettings,
+    lockfile_path: &Path,
+    locked: bool,
+    offline: bool,
+    dry_run: bool,
+    show_changelog: bool,
+    changelog_file: Option<&Path>,
+    report_file: Option<&Path>,
+) -> Result<()> {
+    let mut report = Report {
+        dry_run,
+        ..Report::default()
+    };
+    // Read the previously locked files before they are overwritten, to determine which mods are updated
+    let previous = if show_changelog {
+        lockfile::read(lockfile_path)?
+            .profiles
+            .remove(&profile.name)
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let (to_download, error) = if locked || offline {
+        let locked_files = lockfile::read(lockfile_path)?
+            .profiles
+            .remove(&profile.name)
+            .with_context(|| {
+                if offline {
+                    format!(
+                        "{} has never been upgraded, so there are no files to install offline",
+                        profile.name
+                    )
+                } else {
+                    format!(
+                        "There are no locked files for {}, run `ferium lock` to create them",
+                        profile.name
+                    )
+                }
+            })?;
+        (locked_files, false)
+    } else {
+        let resolution = if dry_run {
+            resolve(profile, settings).await?
+        } else {
+            lock(profile, settings, lockfile_path).await?
+        };
+        report.dependencies_added = resolution.dependencies_added(profile);
+        report.failures = resolution.failures.clone();
+        let error = resolution.error();
+        (resolution.locked, error)
+    };
+    report.resolved = to_download.iter().map(ResolvedFile::from).collect_vec();
+    let changes = if show_changelog {
+        changelog::changes(&previous, &to_download, &profile.output_dir)?
+    } else {
+        Vec::new()
+    };
+    // The report is written even if installing the files fails, so that it shows what was done before then
+    let result = install(profile, to_download, offline, dry_run, &mut report).await;
+    if let Some(report_file) = report_file {
+        report.write_file(report_file)?;
+        println!(
+            "\n{} Wrote the report to {}",
+            &*TICK,
+            report_file.display().to_string().blue().underline()
+        );
+    }
+    result?;
+
+    if show_changelog {
+        if changes.is_empty() {
+            println!("\n{}", "No mods are being updated".bold());
+        } else {
+            eprint!("\n{}", "Fetching changelogs... ".bold());
+            let changelog = changelog::fetch(changes, profile.filters.mod_loader()).await?;
+            eprintln!("{}", &*TICK);
+            changelog::show(&changelog, changelog_file)?;
+            if let Some(changelog_file) = changelog_file {
+                println!(
+                    "{} Wrote the changelogs to {}",
+                    &*TICK,
+                    changelog_file.display().to_string().blue().underline()
+                );
+            }
+        }
+    }
+
+    if error {
+        Err(anyhow!(
+            "\nCould not get the latest compatible version of some mods"
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Install the `locked` files and the mods in the `user` folder of `profile`, recording what was done in `report`
+///
+/// If `offline` is true, the files are installed from the output directory and the download cache only.
+/// If `dry_run` is true, the changes that would be made are printed and nothing on disk is changed.
+async fn install(
+    profile: &Profile,
+    locked: Vec<LockedFile>,
+    offline: bool,
+    dry_run: bool,
+    report: &mut Report,
+) -> Result<()> {
+    let mut to_download = locked
+        .into_iter()
+        // Locked files are downloaded directly to the output directory
+        .map(Downloadable::from)
+        .collect_vec();
     let mut to_install = Vec::new();
     if profile.output_dir.join("user").exists()
         && profile.filters.mod_loader() != Some(&ModLoader::Quilt)
@@ -216,24 +890,36 @@ pub async fn upgrade(profile: &Profile) -> Result<()> {
         }
     }
 
-    clean(&profile.output_dir, &mut to_download, &mut to_install).await?;
-    to_download
-        .iter_mut()
-        // Download directly to the output directory
-        .map(|thing| thing.output = thing.filename().into())
-        .for_each(drop); // Doesn't drop any data, just runs the iterator
-    if to_download.is_empty() && to_install.is_empty() {
-        println!("\n{}", "All up to date!".bold());
-    } else {
-        println!("\n{}\n", "Downloading Mod Files".bold());
-        download(profile.output_dir.clone(), to_download, to_install).await?;
+    if offline {
+        cache::ensure_available(&profile.output_dir, &to_download)?;
     }
 
-    if error {
-        Err(anyhow!(
-            "\nCould not get the latest compatible version of some mods"
-        ))
+    if dry_run {
+        let plan = plan_clean(&profile.output_dir, &mut to_download, &mut to_install)?;
+        println!("\n{}\n", "Dry Run".bold());
+        plan.print();
+        print_downloads(&to_download, &to_install);
+        if plan.is_empty() && to_download.is_empty() && to_install.is_empty() {
+            println!("{}", "All up to date!".bold());
+        }
+        report.record_clean(&plan);
+        report.record_planned_downloads(&to_download);
+        report.record_installs(&to_install);
     } else {
-        Ok(())
+        let plan = clean(&profile.output_dir, &mut to_download, &mut to_install).await?;
+        report.record_clean(&plan);
+        if to_download.is_empty() && to_install.is_empty() {
+            println!("\n{}", "All up to date!".bold());
+        } else {
+            println!("\n{}\n", "Downloading Mod Files".bold());
+            report.record_installs(&to_install);
+            let (downloaded, failures) =
+                download(profile.output_dir.clone(), to_download, to_install).await?;
+            report.downloaded = downloaded;
+            let failed = !failures.is_empty();
+            report.failures.extend(failures);
+            ensure!(!failed, "\nCould not download some files");
+        }
     }
+    Ok(())
 }
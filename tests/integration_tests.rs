@@ -1,8 +1,16 @@
 mod util;
 
 use libium::HOME;
-use std::fs::remove_dir;
-use util::run_command;
+use std::{
+    env::current_dir,
+    fs::{create_dir_all, read_to_string, remove_dir, remove_dir_all, remove_file, write, File},
+    path::Path,
+};
+use util::{
+    locked_file, lockfile, run_command, run_command_in, run_command_output,
+    run_command_with_lockfile, run_commands, serve, TEST_FILE,
+};
+use zip::ZipArchive;
 
 type Result = std::io::Result<()>;
 
@@ -186,6 +194,80 @@ fn modpack_add_curseforge() -> Result {
     )
 }
 
+#[test]
+fn modpack_add_local_upgrade() -> Result {
+    let _ = remove_dir_all("./tests/isolated/local_modpack");
+    let output_dir = current_dir()?.join("tests/isolated/local_modpack");
+    run_commands(
+        vec![
+            vec![
+                "modpack",
+                "add",
+                "./tests/test_modpacks/local.mrpack",
+                "--output-dir",
+                &output_dir.to_string_lossy(),
+                "--install-overrides",
+                "true",
+            ],
+            vec!["modpack", "upgrade"],
+            vec!["modpack", "list"],
+        ],
+        Some("empty_profile"),
+    )?;
+    // The override is installed from the local file without downloading the modpack
+    assert!(output_dir.join("config").join("local.txt").is_file());
+    Ok(())
+}
+
+#[test]
+fn modpack_upgrade_local_instance() -> Result {
+    let _ = remove_dir_all("./tests/isolated/local_instance");
+    let instance_dir = current_dir()?.join("tests/isolated/local_instance");
+    run_commands(
+        vec![
+            vec![
+                "modpack",
+                "add",
+                "./tests/test_modpacks/local.mrpack",
+                "--output-dir",
+                &instance_dir.join(".minecraft").to_string_lossy(),
+                "--install-overrides",
+                "true",
+            ],
+            vec!["modpack", "upgrade", "--instance"],
+        ],
+        Some("empty_profile"),
+    )?;
+    let pack: serde_json::Value =
+        serde_json::from_str(&read_to_string(instance_dir.join("mmc-pack.json"))?)?;
+    assert_eq!(pack["formatVersion"], 1);
+    let components = pack["components"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|component| {
+            (
+                component["uid"].as_str().unwrap(),
+                component["version"].as_str().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        components,
+        [
+            ("net.minecraft", "1.21.1"),
+            ("net.fabricmc.intermediary", "1.21.1"),
+            ("net.fabricmc.fabric-loader", "0.16.5"),
+        ]
+    );
+    assert_eq!(pack["components"][1]["dependencyOnly"], true);
+    assert_eq!(pack["components"][2]["cachedVersion"], "0.16.5");
+    let instance_cfg = read_to_string(instance_dir.join("instance.cfg"))?;
+    assert!(instance_cfg.contains("InstanceType=OneSix"));
+    assert!(instance_cfg.contains("name=Local Test Modpack"));
+    Ok(())
+}
+
 #[test]
 fn already_added() {
     assert!(run_command(vec!["add", "StArLiGhT"], Some("one_profile_full")).is_ok());
@@ -203,6 +285,70 @@ fn list_verbose() -> Result {
     run_command(vec!["list", "--verbose"], Some("one_profile_full"))
 }
 
+#[test]
+fn list_json() -> Result {
+    let list: serde_json::Value = serde_json::from_str(&run_command_output(
+        vec!["list", "--format", "json"],
+        Some("one_profile_full"),
+        None,
+    )?)?;
+    assert_eq!(list["name"], "Default Modded");
+    assert_eq!(list["mod_loader"], "Fabric");
+    assert_eq!(list["game_versions"], serde_json::json!(["1.18.2"]));
+    let mods = list["mods"].as_array().unwrap();
+    // The mods are sorted by name
+    let names = mods
+        .iter()
+        .map(|mod_| mod_["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Incendium", "sodium", "Starlight (Fabric)"]);
+    assert_eq!(mods[0]["platform"], "CurseForge");
+    assert_eq!(mods[0]["identifier"], "591388");
+    assert_eq!(mods[1]["platform"], "GitHub");
+    assert_eq!(mods[2]["identifier"], "H8CaAYZC");
+    // The details are only included in verbose mode
+    assert!(mods
+        .iter()
+        .all(|mod_| mod_.get("description").is_none() && mod_.get("pinned_to").is_none()));
+    Ok(())
+}
+
+#[test]
+fn list_verbose_json() -> Result {
+    let list: serde_json::Value = serde_json::from_str(&run_command_output(
+        vec!["list", "--verbose", "--format", "json"],
+        Some("one_profile_full"),
+        None,
+    )?)?;
+    let mods = list["mods"].as_array().unwrap();
+    assert_eq!(mods.len(), 3);
+    for mod_ in mods {
+        // The details are flattened into the mod
+        assert!(
+            mod_["name"].is_string() && mod_["platform"].is_string(),
+            "{mod_}"
+        );
+        assert!(mod_["description"].is_string(), "{mod_}");
+        assert!(
+            mod_["link"].as_str().unwrap().starts_with("https://"),
+            "{mod_}"
+        );
+        assert!(mod_["downloads"].as_u64().unwrap() > 0, "{mod_}");
+        assert!(!mod_["authors"].as_array().unwrap().is_empty(), "{mod_}");
+        assert!(mod_["categories"].is_array(), "{mod_}");
+        assert!(mod_.get("details").is_none(), "{mod_}");
+    }
+    Ok(())
+}
+
+#[test]
+fn list_verbose_refresh() -> Result {
+    run_command(
+        vec!["list", "--verbose", "--refresh"],
+        Some("one_profile_full"),
+    )
+}
+
 #[test]
 fn list_markdown() -> Result {
     run_command(
@@ -222,6 +368,450 @@ fn upgrade() -> Result {
     run_command(vec!["upgrade"], Some("one_profile_full"))
 }
 
+#[test]
+fn upgrade_dry_run() -> Result {
+    run_command(vec!["upgrade", "--dry-run"], Some("one_profile_full"))
+}
+
+#[test]
+fn upgrade_dry_run_changelog() -> Result {
+    // Starlight was previously locked to an older file that is still installed, so it is being updated
+    let _ = remove_dir_all("./tests/isolated/changelog");
+    let _ = remove_file("./tests/changelog.md");
+    create_dir_all("./tests/isolated/changelog")?;
+    write("./tests/isolated/changelog/starlight-old.jar", "test")?;
+    let mut starlight = locked_file(
+        "Starlight (Fabric)",
+        "H8CaAYZC",
+        "http://127.0.0.1:1/starlight-old.jar",
+    );
+    starlight["file_id"] = "old".into();
+    run_command_in(
+        vec![
+            "upgrade",
+            "--dry-run",
+            "--changelog",
+            "./tests/changelog.md",
+        ],
+        Some("one_profile_full"),
+        Some(&lockfile(vec![starlight])),
+        Some("./tests/isolated/changelog"),
+        None,
+    )?;
+    assert!(read_to_string("./tests/changelog.md")?.contains("starlight-old.jar → "));
+    Ok(())
+}
+
+#[test]
+fn upgrade_dry_run_changelog_without_lockfile() -> Result {
+    // Without a lockfile, mods that are not installed are shown with an unknown old version
+    let _ = remove_dir_all("./tests/isolated/changelog_unlocked");
+    let _ = remove_file("./tests/changelog_unlocked.md");
+    create_dir_all("./tests/isolated/changelog_unlocked")?;
+    run_command_in(
+        vec![
+            "upgrade",
+            "--dry-run",
+            "--changelog",
+            "./tests/changelog_unlocked.md",
+        ],
+        Some("one_profile_full"),
+        None,
+        Some("./tests/isolated/changelog_unlocked"),
+        None,
+    )?;
+    assert!(read_to_string("./tests/changelog_unlocked.md")?.contains("Unknown installed version"));
+    Ok(())
+}
+
+#[test]
+fn upgrade_dry_run_report() -> Result {
+    run_command(
+        vec!["upgrade", "--dry-run", "--report", "./tests/report.json"],
+        Some("one_profile_full"),
+    )?;
+    let report: serde_json::Value = serde_json::from_str(&read_to_string("./tests/report.json")?)?;
+    assert_eq!(report["dry_run"], true);
+    let resolved = report["resolved"].as_array().unwrap();
+    assert!(!resolved.is_empty());
+    assert!(resolved
+        .iter()
+        .all(|file| file["name"].is_string() && file["filename"].is_string()));
+    assert!(report["downloaded"].is_array());
+    Ok(())
+}
+
+#[test]
+fn lock() -> Result {
+    // Installing the locked files fails if nothing was written to the lockfile
+    let _ = remove_file("./tests/lock_report.json");
+    run_commands(
+        vec![
+            vec!["lock"],
+            vec![
+                "upgrade",
+                "--locked",
+                "--dry-run",
+                "--report",
+                "./tests/lock_report.json",
+            ],
+        ],
+        Some("one_profile_full"),
+    )?;
+    let report: serde_json::Value =
+        serde_json::from_str(&read_to_string("./tests/lock_report.json")?)?;
+    let resolved = report["resolved"].as_array().unwrap();
+    for name in ["Incendium", "sodium", "Starlight (Fabric)"] {
+        assert!(
+            resolved.iter().any(|file| file["name"] == name),
+            "{name} is not locked"
+        );
+    }
+    assert!(resolved
+        .iter()
+        .all(|file| file["filename"].as_str().unwrap().ends_with(".jar")));
+    Ok(())
+}
+
+#[test]
+fn outdated() -> Result {
+    // Starlight was locked to an older file that is still installed, so it has an update available
+    let output_dir = "./tests/isolated/outdated";
+    let _ = remove_dir_all(output_dir);
+    create_dir_all(output_dir)?;
+    write(format!("{output_dir}/starlight-old.jar"), "test")?;
+    let mut starlight = locked_file(
+        "Starlight (Fabric)",
+        "H8CaAYZC",
+        "http://127.0.0.1:1/starlight-old.jar",
+    );
+    starlight["file_id"] = "old".into();
+    let err = run_command_in(
+        vec!["outdated"],
+        Some("one_profile_full"),
+        Some(&lockfile(vec![starlight])),
+        Some(output_dir),
+        None,
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(err.contains("exit code Some(2)"), "{err}");
+    assert!(err.contains("starlight-old.jar"), "{err}");
+    Ok(())
+}
+
+#[test]
+fn outdated_up_to_date() -> Result {
+    // Exits with code 0 once the latest files are installed
+    let output_dir = "./tests/isolated/up_to_date";
+    let _ = remove_dir_all(output_dir);
+    run_command_in(
+        vec!["upgrade"],
+        Some("one_profile_full"),
+        None,
+        Some(output_dir),
+        None,
+    )?;
+    run_command_in(
+        vec!["outdated"],
+        Some("one_profile_full"),
+        None,
+        Some(output_dir),
+        None,
+    )
+}
+
+#[test]
+fn tree() -> Result {
+    let tree = run_command_output(vec!["tree"], Some("one_profile_full"), None)?;
+    // The configured mods are printed at the top level, sorted by name, with their resolved files
+    let top_level = tree
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with(['├', '└', '│', ' ']))
+        .skip_while(|line| !line.starts_with("Incendium"))
+        .collect::<Vec<_>>();
+    assert_eq!(top_level.len(), 3, "{tree}");
+    for (line, name) in top_level
+        .iter()
+        .zip(["Incendium", "sodium", "Starlight (Fabric)"])
+    {
+        assert!(line.starts_with(&format!("{name}  ")), "{tree}");
+        assert!(line.ends_with(".jar"), "{tree}");
+    }
+    Ok(())
+}
+
+#[test]
+fn upgrade_locked_without_lockfile() {
+    // This should fail as the profile has not been locked yet
+    assert!(run_command(vec!["upgrade", "--locked"], Some("one_profile_full")).is_err());
+}
+
+#[test]
+fn upgrade_locked_retries_rate_limited_downloads() -> Result {
+    // Rate limit the first two requests, and then serve the file
+    let port = serve(|i, _| {
+        if i < 2 {
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        } else {
+            TEST_FILE
+        }
+    })?;
+
+    run_command_in(
+        vec!["--retries", "2", "upgrade", "--locked"],
+        Some("one_profile_full"),
+        Some(&lockfile(vec![locked_file(
+            "Rate limited",
+            "test",
+            &format!("http://127.0.0.1:{port}/rate-limited.jar"),
+        )])),
+        Some("./tests/isolated/rate_limited"),
+        None,
+    )
+}
+
+#[test]
+fn upgrade_locked_verifies_sha512_and_md5() -> Result {
+    let port = serve(|_, _| TEST_FILE)?;
+    let mut sha512 = locked_file(
+        "SHA-512",
+        "sha512",
+        &format!("http://127.0.0.1:{port}/sha512.jar"),
+    );
+    sha512["sha512"] = "ee26b0dd4af7e749aa1a8ee3c10ae9923f618980772e473f8819a5d4940e0db27ac185f8a0e1d5f84f88bc887fd67b143732c304cc5fa9ad8e6f57f50028a8ff".into();
+    let mut md5 = locked_file("MD5", "md5", &format!("http://127.0.0.1:{port}/md5.jar"));
+    md5["project"] = serde_json::json!({"CurseForgeProject": 1});
+    md5["file_id"] = "1".into();
+    md5["md5"] = "00000000000000000000000000000000".into();
+
+    let _ = remove_dir_all("./tests/isolated/verified");
+    create_dir_all("./tests/isolated/verified")?;
+    let report_file = "./tests/isolated/verified/report.json";
+    // This should fail as the MD5 hash of one of the files does not match
+    assert!(run_command_in(
+        vec!["upgrade", "--locked", "--report", report_file],
+        Some("one_profile_full"),
+        Some(&lockfile(vec![sha512, md5])),
+        Some("./tests/isolated/verified/mods"),
+        None,
+    )
+    .is_err());
+
+    let report: serde_json::Value = serde_json::from_str(&read_to_string(report_file)?)?;
+    assert_eq!(report["downloaded"][0]["filename"], "sha512.jar");
+    assert_eq!(report["failures"][0]["name"], "md5.jar");
+    assert!(report["failures"][0]["error"]
+        .as_str()
+        .unwrap()
+        .contains("The MD5 hash of md5.jar"));
+    Ok(())
+}
+
+#[test]
+fn upgrade_locked_resumes_partial_downloads() -> Result {
+    // Only serve the rest of the file if the download is resumed
+    let port = serve(|_, request| {
+        if request.to_lowercase().contains("range: bytes=2-") {
+            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 2-3/4\r\nContent-Length: 2\r\nConnection: close\r\n\r\nst"
+        } else {
+            "HTTP/1.1 200 OK\r\nContent-Length: 4\r\nConnection: close\r\n\r\nfail"
+        }
+    })?;
+    let mut resumed = locked_file(
+        "Resumed",
+        "test",
+        &format!("http://127.0.0.1:{port}/resumed.jar"),
+    );
+    resumed["sha1"] = "a94a8fe5ccb19ba61c4c0873d391e987982fbbd3".into();
+
+    create_dir_all("./tests/isolated/resumed")?;
+    write("./tests/isolated/resumed/resumed.jar.part", "te")?;
+    run_command_in(
+        vec!["upgrade", "--locked"],
+        Some("one_profile_full"),
+        Some(&lockfile(vec![resumed])),
+        Some("./tests/isolated/resumed"),
+        None,
+    )
+}
+
+#[test]
+fn upgrade_locked_failed_download_report() -> Result {
+    // Serve one of the files, and fail to find the other
+    let port = serve(|_, request| {
+        if request.contains("/found.jar") {
+            TEST_FILE
+        } else {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        }
+    })?;
+
+    let _ = remove_dir_all("./tests/isolated/failed_download");
+    create_dir_all("./tests/isolated/failed_download")?;
+    let report_file = "./tests/isolated/failed_download/report.json";
+    // This should fail as one of the files could not be downloaded
+    assert!(run_command_in(
+        vec!["upgrade", "--locked", "--report", report_file],
+        Some("one_profile_full"),
+        Some(&lockfile(vec![
+            locked_file(
+                "Found",
+                "found",
+                &format!("http://127.0.0.1:{port}/found.jar"),
+            ),
+            locked_file(
+                "Missing",
+                "missing",
+                &format!("http://127.0.0.1:{port}/missing.jar"),
+            ),
+        ])),
+        Some("./tests/isolated/failed_download/mods"),
+        None,
+    )
+    .is_err());
+
+    // The report still records the file that was downloaded, along with the one that failed
+    let report: serde_json::Value = serde_json::from_str(&read_to_string(report_file)?)?;
+    assert_eq!(report["dry_run"], false);
+    assert_eq!(report["downloaded"][0]["filename"], "found.jar");
+    assert_eq!(report["failures"][0]["name"], "missing.jar");
+    assert!(Path::new("./tests/isolated/failed_download/mods/found.jar").is_file());
+    Ok(())
+}
+
+#[test]
+fn upgrade_offline() -> Result {
+    // The file is already in the output directory, so nothing has to be downloaded
+    create_dir_all("./tests/isolated/offline")?;
+    write("./tests/isolated/offline/offline.jar", "test")?;
+    run_command_in(
+        vec!["--offline", "upgrade"],
+        Some("one_profile_full"),
+        Some(&lockfile(vec![locked_file(
+            "Offline",
+            "test",
+            "http://127.0.0.1:1/offline.jar",
+        )])),
+        Some("./tests/isolated/offline"),
+        None,
+    )
+}
+
+#[test]
+fn upgrade_offline_never_downloaded() {
+    // This should fail as the file is neither in the output directory nor the cache
+    assert!(run_command_with_lockfile(
+        vec!["--offline", "upgrade"],
+        Some("one_profile_full"),
+        Some(&lockfile(vec![locked_file(
+            "Missing",
+            "test",
+            "http://127.0.0.1:1/missing.jar",
+        )])),
+    )
+    .is_err());
+}
+
+#[test]
+fn upgrade_offline_never_upgraded() {
+    // This should fail as there are no files recorded for the profile
+    assert!(run_command_with_lockfile(
+        vec!["--offline", "upgrade"],
+        Some("one_profile_full"),
+        Some(r#"{"profiles":{}}"#),
+    )
+    .is_err());
+}
+
+#[test]
+fn cache_stats() -> Result {
+    let home = "./tests/isolated/cache_stats";
+    let _ = remove_dir_all(home);
+    let group = format!("{home}/.config/ferium/.cache/files/a9");
+    create_dir_all(&group)?;
+    write(
+        format!("{group}/a94a8fe5ccb19ba61c4c0873d391e987982fbbd3"),
+        "test",
+    )?;
+    write(
+        format!("{group}/a94a8fe5ccb19ba61c4c0873d391e987982fbbd4"),
+        "test",
+    )?;
+    let stats = run_command_output(vec!["cache", "stats"], Some("empty"), Some(home))?;
+    let value = |label: &str| {
+        stats
+            .lines()
+            .find_map(|line| line.strip_prefix(label))
+            .map(str::trim)
+            .unwrap_or_default()
+            .to_owned()
+    };
+    assert_eq!(value("Files:"), "2", "{stats}");
+    assert!(value("Size:").starts_with("8 "), "{stats}");
+    assert!(value("Location:").ends_with("files"), "{stats}");
+    Ok(())
+}
+
+#[test]
+fn cache_prune() -> Result {
+    let home = "./tests/isolated/cache_prune";
+    let _ = remove_dir_all(home);
+    let group = format!("{home}/.config/ferium/.cache/files/a9");
+    let cached = format!("{group}/a94a8fe5ccb19ba61c4c0873d391e987982fbbd3");
+    create_dir_all(&group)?;
+    write(&cached, "test")?;
+
+    // The file was just cached, so it is kept by default
+    run_command_output(vec!["cache", "prune"], Some("empty"), Some(home))?;
+    assert!(Path::new(&cached).is_file());
+    let pruned = run_command_output(
+        vec!["cache", "prune", "--older-than", "0"],
+        Some("empty"),
+        Some(home),
+    )?;
+    assert!(!Path::new(&cached).exists());
+    assert!(pruned.contains("Deleted 1 cached file(s)"), "{pruned}");
+    Ok(())
+}
+
+#[test]
+fn rollback_missing_generation() {
+    // This should fail as the output directory has not been upgraded this many times
+    assert!(run_command(vec!["rollback", "--to", "9999"], Some("one_profile_full")).is_err());
+}
+
+#[test]
+fn rollback_prune() -> Result {
+    let output_dir = "./tests/isolated/rollback_prune";
+    let _ = remove_dir_all(output_dir);
+    for generation in 1..=5 {
+        create_dir_all(format!("{output_dir}/.old/{generation}"))?;
+        write(
+            format!("{output_dir}/.old/{generation}/mod-{generation}.jar"),
+            "test",
+        )?;
+    }
+    run_command_in(
+        vec!["rollback", "--prune", "3"],
+        Some("one_profile_full"),
+        None,
+        Some(output_dir),
+        None,
+    )?;
+    // Only the latest 3 generations are kept
+    for generation in 1..=2 {
+        assert!(!Path::new(&format!("{output_dir}/.old/{generation}")).exists());
+    }
+    for generation in 3..=5 {
+        assert!(Path::new(&format!(
+            "{output_dir}/.old/{generation}/mod-{generation}.jar"
+        ))
+        .is_file());
+    }
+    Ok(())
+}
+
 #[test]
 fn cf_modpack_upgrade() -> Result {
     let _ = remove_dir("./tests/cf_modpack");
@@ -234,6 +824,191 @@ fn md_modpack_upgrade() -> Result {
     run_command(vec!["modpack", "upgrade"], Some("two_modpacks_mdactive"))
 }
 
+#[test]
+fn md_modpack_upgrade_dry_run() -> Result {
+    let _ = remove_dir_all("./tests/isolated/modpack_dry_run");
+    run_command_in(
+        vec!["modpack", "upgrade", "--dry-run"],
+        Some("two_modpacks_mdactive"),
+        None,
+        Some("./tests/isolated/modpack_dry_run/output"),
+        Some("./tests/isolated/modpack_dry_run/home"),
+    )?;
+    // Neither the output directory nor the cache in the home directory should have been created
+    assert!(!Path::new("./tests/isolated/modpack_dry_run/output").exists());
+    assert!(!Path::new("./tests/isolated/modpack_dry_run/home").exists());
+    Ok(())
+}
+
+#[test]
+fn md_modpack_upgrade_dry_run_report() -> Result {
+    run_command(
+        vec![
+            "modpack",
+            "upgrade",
+            "--dry-run",
+            "--report",
+            "./tests/modpack_report.json",
+        ],
+        Some("two_modpacks_mdactive"),
+    )?;
+    let report: serde_json::Value =
+        serde_json::from_str(&read_to_string("./tests/modpack_report.json")?)?;
+    assert_eq!(report["dry_run"], true);
+    let resolved = report["resolved"].as_array().unwrap();
+    assert!(!resolved.is_empty());
+    // Modrinth modpacks have no files that third parties are denied from downloading
+    assert!(report["failures"].as_array().unwrap().is_empty());
+    Ok(())
+}
+
+#[test]
+fn md_modpack_upgrade_instance_not_minecraft_dir() {
+    assert!(run_command(
+        vec!["modpack", "upgrade", "--instance"],
+        Some("two_modpacks_mdactive"),
+    )
+    .is_err());
+}
+
+#[test]
+fn profile_export_mrpack() -> Result {
+    let _ = remove_file("./tests/export.mrpack");
+    run_command(
+        vec!["profile", "export", "--output", "./tests/export.mrpack"],
+        Some("one_profile_full"),
+    )?;
+    let mut zip = ZipArchive::new(File::open("./tests/export.mrpack")?)?;
+    let index: serde_json::Value = serde_json::from_reader(zip.by_name("modrinth.index.json")?)?;
+    assert_eq!(index["formatVersion"], 1);
+    assert_eq!(index["game"], "minecraft");
+    assert_eq!(index["dependencies"]["minecraft"], "1.18.2");
+    assert!(index["dependencies"]["fabric-loader"].is_string());
+    // Starlight is downloaded from Modrinth and Sodium from GitHub
+    let files = index["files"].as_array().unwrap();
+    assert_eq!(files.len(), 2, "{index}");
+    for file in files {
+        assert!(
+            file["path"].as_str().unwrap().starts_with("mods/"),
+            "{file}"
+        );
+        assert!(file["hashes"]["sha1"].is_string(), "{file}");
+        assert!(file["hashes"]["sha512"].is_string(), "{file}");
+        assert!(file["fileSize"].as_u64().unwrap() > 0, "{file}");
+    }
+    // Incendium is from CurseForge, which modpacks cannot download from, so it is embedded instead
+    assert!(zip
+        .file_names()
+        .any(|name| name.starts_with("overrides/mods/") && name.ends_with(".jar")));
+    Ok(())
+}
+
+#[test]
+fn profile_export_curseforge() -> Result {
+    let _ = remove_file("./tests/export.zip");
+    run_command(
+        vec![
+            "profile",
+            "export",
+            "--format",
+            "curseforge",
+            "--output",
+            "./tests/export.zip",
+        ],
+        Some("one_profile_full"),
+    )?;
+    let mut zip = ZipArchive::new(File::open("./tests/export.zip")?)?;
+    let manifest: serde_json::Value = serde_json::from_reader(zip.by_name("manifest.json")?)?;
+    assert_eq!(manifest["manifestType"], "minecraftModpack");
+    assert_eq!(manifest["overrides"], "overrides");
+    assert_eq!(manifest["minecraft"]["version"], "1.18.2");
+    let loader = &manifest["minecraft"]["modLoaders"][0];
+    assert!(
+        loader["id"].as_str().unwrap().starts_with("fabric-"),
+        "{manifest}"
+    );
+    assert_eq!(loader["primary"], true);
+    // Only Incendium is from CurseForge, the other mods are embedded
+    let files = manifest["files"].as_array().unwrap();
+    assert_eq!(files.len(), 1, "{manifest}");
+    assert_eq!(files[0]["projectID"], 591388);
+    assert!(files[0]["fileID"].as_i64().unwrap() > 0);
+    assert_eq!(
+        zip.file_names()
+            .filter(|name| name.starts_with("overrides/mods/"))
+            .count(),
+        2
+    );
+    Ok(())
+}
+
+#[test]
+fn profile_configure_side() -> Result {
+    run_command(
+        vec!["profile", "configure", "--side", "server"],
+        Some("one_profile_full"),
+    )
+}
+
+#[test]
+fn profile_configure_rename_keeps_lockfile() -> Result {
+    // Installing the locked files fails if the lockfile has no entry for the renamed profile
+    run_commands(
+        vec![
+            vec!["lock"],
+            vec!["profile", "configure", "--name", "Renamed"],
+            vec!["upgrade", "--locked", "--dry-run"],
+        ],
+        Some("one_profile_full"),
+    )
+}
+
+#[test]
+fn upgrade_server_skips_client_only() -> Result {
+    // Mod Menu is client only, while Fabric API supports servers
+    let _ = remove_file("./tests/server_report.json");
+    run_commands(
+        vec![
+            vec!["profile", "configure", "--side", "server"],
+            vec!["add", "mOgUt4GM"],
+            vec!["add", "P7dR8mSH"],
+            vec![
+                "upgrade",
+                "--dry-run",
+                "--report",
+                "./tests/server_report.json",
+            ],
+        ],
+        Some("empty_profile"),
+    )?;
+    let report: serde_json::Value =
+        serde_json::from_str(&read_to_string("./tests/server_report.json")?)?;
+    let names = report["resolved"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| file["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Fabric API"], "{report}");
+    Ok(())
+}
+
+#[test]
+fn profile_configure_optional_dependencies() -> Result {
+    run_command(
+        vec!["profile", "configure", "--optional-dependencies", "all"],
+        Some("one_profile_full"),
+    )
+}
+
+#[test]
+fn modpack_configure_side() -> Result {
+    run_command(
+        vec!["modpack", "configure", "--side", "server"],
+        Some("two_modpacks_mdactive"),
+    )
+}
+
 #[test]
 fn profile_switch() -> Result {
     run_command(
@@ -250,6 +1025,109 @@ fn modpack_switch() -> Result {
     )
 }
 
+#[test]
+fn profile_switch_no_input() {
+    // This should fail as the profile to switch to cannot be picked
+    let err = run_command(
+        vec!["--no-input", "profile", "switch"],
+        Some("two_profiles_one_empty"),
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string().contains("provide its name instead"),
+        "{err}"
+    );
+}
+
+#[test]
+fn remove_no_input() {
+    // This should fail as the mods to remove cannot be picked
+    let err = run_command(vec!["--yes", "remove"], Some("one_profile_full")).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("provide their names or IDs instead"),
+        "{err}"
+    );
+}
+
+#[test]
+fn modpack_add_no_input() -> Result {
+    // Overrides are installed by default, so only the output directory has to be provided
+    run_command(
+        vec![
+            "--no-input",
+            "modpack",
+            "add",
+            "1KVo5zza",
+            "--output-dir",
+            &output_dir(),
+        ],
+        Some("empty_profile"),
+    )
+}
+
+#[test]
+fn modpack_add_no_input_missing_output_dir() {
+    // This should fail as the output directory has no default
+    let err = run_command(
+        vec!["--no-input", "modpack", "add", "1KVo5zza"],
+        Some("empty_profile"),
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string().contains("provide `--output-dir` instead"),
+        "{err}"
+    );
+}
+
+#[test]
+fn pin_curseforge() -> Result {
+    run_command(vec!["pin", "591388", "4772286"], Some("one_profile_full"))
+}
+
+#[test]
+fn pin_curseforge_wrong_file() {
+    // This should fail as the file does not belong to the mod
+    let err = run_command(vec!["pin", "591388", "1"], Some("one_profile_full")).unwrap_err();
+    assert!(err.to_string().contains("There is no file 1"), "{err}");
+}
+
+#[test]
+fn pin_github_missing_tag() {
+    // This should fail as the release does not exist, instead of saving a pin that cannot be upgraded
+    let err = run_command(
+        vec!["pin", "CaffeineMC/sodium", "not-a-release"],
+        Some("one_profile_full"),
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("There is no release not-a-release"),
+        "{err}"
+    );
+}
+
+#[test]
+fn remove_pinned() {
+    // This should fail as the pin is removed along with the mod, so the mod is not pinned once added again
+    let err = run_commands(
+        vec![
+            vec!["pin", "591388", "4772286"],
+            vec!["remove", "591388"],
+            vec!["add", "591388"],
+            vec!["unpin", "591388"],
+        ],
+        Some("one_profile_full"),
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("is not pinned"), "{err}");
+}
+
+#[test]
+fn unpin_not_pinned() {
+    assert!(run_command(vec!["unpin", "incendium"], Some("one_profile_full")).is_err());
+}
+
 #[test]
 fn remove_fail() {
     // These should fail as one of the mod names provided does not exist
@@ -1,25 +1,149 @@
+use serde_json::{json, Value};
 use std::{
-    fs::{copy, create_dir},
-    io::{Error, ErrorKind, Result},
+    fs::{create_dir, read_to_string, write},
+    io::{Error, ErrorKind, Read as _, Result, Write as _},
+    net::TcpListener,
     process::Command,
+    thread,
 };
 
+/// The response of a server made with [`serve`] that serves a 4 byte file containing `test`
+pub const TEST_FILE: &str = "HTTP/1.1 200 OK\r\nContent-Length: 4\r\nConnection: close\r\n\r\ntest";
+
+/// Serve HTTP on a local port in the background and return the port
+///
+/// The `i`th request is answered with `respond(i, request)`.
+pub fn serve(respond: impl Fn(usize, &str) -> &'static str + Send + 'static) -> Result<u16> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    thread::spawn(move || {
+        for (i, stream) in listener.incoming().enumerate() {
+            let Ok(mut stream) = stream else {
+                continue;
+            };
+            let mut request = [0; 1024];
+            let read = stream.read(&mut request).unwrap_or_default();
+            let response = respond(i, &String::from_utf8_lossy(&request[..read]));
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    Ok(port)
+}
+
+/// Get the lock entry of a 4 byte file downloaded from `url` for the Modrinth project `project`
+///
+/// The entry can be changed afterwards, e.g. to add hashes.
+pub fn locked_file(name: &str, project: &str, url: &str) -> Value {
+    json!({
+        "name": name,
+        "project": {"ModrinthProject": project},
+        "file_id": "test",
+        "url": url,
+        "filename": url.rsplit('/').next().unwrap(),
+        "size": 4,
+    })
+}
+
+/// Get a lockfile with `files` locked for the profile of the `one_profile_full` config
+pub fn lockfile(files: Vec<Value>) -> String {
+    json!({"profiles": {"Default Modded": files}}).to_string()
+}
+
 pub fn run_command(args: Vec<&str>, config_file: Option<&str>) -> Result<()> {
-    let running = format!("./tests/configs/running/{}.json", rand::random::<u16>());
+    run_command_with_lockfile(args, config_file, None)
+}
+
+/// Run ferium like [`run_command`], with `lockfile` written to the lockfile next to the config file if provided
+pub fn run_command_with_lockfile(
+    args: Vec<&str>,
+    config_file: Option<&str>,
+    lockfile: Option<&str>,
+) -> Result<()> {
+    run_command_in(args, config_file, lockfile, None, None)
+}
+
+/// Run ferium like [`run_command_with_lockfile`], with the output directories of the profiles and modpacks
+/// replaced by `output_dir`, and the home directory (which contains the cache) replaced by `home` if provided
+///
+/// Use this for tests that depend on the contents of these directories,
+/// as the other tests change them in parallel.
+pub fn run_command_in(
+    args: Vec<&str>,
+    config_file: Option<&str>,
+    lockfile: Option<&str>,
+    output_dir: Option<&str>,
+    home: Option<&str>,
+) -> Result<()> {
+    let running = write_running(config_file, lockfile, output_dir)?;
+    run(&running, args, home).map(drop)
+}
+
+/// Run ferium like [`run_command_in`] without a lockfile or output directory, and return what it printed to stdout
+pub fn run_command_output(
+    args: Vec<&str>,
+    config_file: Option<&str>,
+    home: Option<&str>,
+) -> Result<String> {
+    let running = write_running(config_file, None, None)?;
+    run(&running, args, home)
+}
+
+/// Run ferium with each of `commands` in turn, all using the same copy of `config_file`
+///
+/// Use this for tests where a command depends on the changes made by the previous ones.
+pub fn run_commands(commands: Vec<Vec<&str>>, config_file: Option<&str>) -> Result<()> {
+    let running = write_running(config_file, None, None)?;
+    for args in commands {
+        run(&running, args, None)?;
+    }
+    Ok(())
+}
+
+/// Copy `config_file` and `lockfile` to the running directory, replacing the output directories with `output_dir`,
+/// and return the path of the copied config file
+fn write_running(
+    config_file: Option<&str>,
+    lockfile: Option<&str>,
+    output_dir: Option<&str>,
+) -> Result<String> {
+    let id = rand::random::<u16>();
+    let running = format!("./tests/configs/running/{id}.json");
     if let Some(config_file) = config_file {
         let _ = create_dir("./tests/configs/running");
-        let template = format!("./tests/configs/{config_file}.json");
-        copy(template, &running)?;
+        let mut config: serde_json::Value = serde_json::from_str(&read_to_string(format!(
+            "./tests/configs/{config_file}.json"
+        ))?)?;
+        if let Some(output_dir) = output_dir {
+            for key in ["profiles", "modpacks"] {
+                for entry in config[key].as_array_mut().into_iter().flatten() {
+                    entry["output_dir"] = output_dir.into();
+                }
+            }
+        }
+        write(&running, serde_json::to_string(&config)?)?;
+    }
+    if let Some(lockfile) = lockfile {
+        let _ = create_dir("./tests/configs/running");
+        write(format!("./tests/configs/running/{id}.lock.json"), lockfile)?;
     }
+    Ok(running)
+}
 
+/// Run ferium with `args` using the config file at `running`, and `home` as the home directory if provided
+///
+/// Returns what ferium printed to stdout, without colours.
+fn run(running: &str, args: Vec<&str>, home: Option<&str>) -> Result<String> {
     let mut command = Command::new(env!("CARGO_BIN_EXE_ferium"));
-    let mut arguments = vec!["--config-file", &running];
+    let mut arguments = vec!["--config-file", running];
     arguments.extend(args);
-    command.args(arguments);
+    command.args(arguments).env("NO_COLOR", "1");
+    if let Some(home) = home {
+        command.env("HOME", home);
+    }
     let output = command.output()?;
 
     if output.status.success() {
-        Ok(())
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
     } else {
         Err(Error::new(
             ErrorKind::Other,